@@ -0,0 +1,52 @@
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+const DAYS: u8 = 25;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=input");
+    println!("cargo:rerun-if-changed=src");
+
+    let mut tests = String::new();
+
+    for day in 1..=DAYS {
+        writeln!(tests, "mod day{:0>2} {{", day).unwrap();
+
+        for part in 1..=2 {
+            let example = format!("input/{:0>2}_test.txt", day);
+            if !is_implemented(day, part) {
+                writeln!(tests, "    #[ignore = \"not implemented\"]").unwrap();
+            } else if !Path::new(&example).exists() {
+                writeln!(tests, "    #[ignore = \"{} is missing\"]", example).unwrap();
+            }
+            writeln!(tests, "    #[test]").unwrap();
+            writeln!(tests, "    fn part{}_example() {{ crate::day{:0>2}::day{:0>2}().assert_example({}, &crate::harness::Context::default()); }}", part, day, day, part).unwrap();
+
+            if !is_implemented(day, part) {
+                writeln!(tests, "    #[ignore = \"not implemented\"]").unwrap();
+            } else if Path::new(&format!("input/{:0>2}_part{}_answer.txt", day, part)).exists() {
+                writeln!(tests, "    #[ignore = \"runs on the actual input\"]").unwrap();
+            } else {
+                writeln!(tests, "    #[ignore = \"no recorded answer\"]").unwrap();
+            }
+            writeln!(tests, "    #[test]").unwrap();
+            writeln!(tests, "    fn part{}_actual() {{ crate::day{:0>2}::day{:0>2}().assert_actual({}, &crate::harness::Context::default()); }}", part, day, day, part).unwrap();
+        }
+
+        writeln!(tests, "}}").unwrap();
+    }
+
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("day_tests.rs");
+    fs::write(path, tests).unwrap();
+}
+
+/// Whether the day registers a real part rather than the `EmptyPart` placeholder, judging by its `Day::new` call.
+fn is_implemented(day: u8, part: usize) -> bool {
+    let source = fs::read_to_string(format!("src/day{:0>2}.rs", day)).unwrap_or_default();
+    let Some(start) = source.find("Day::new(") else { return false };
+
+    source[start..].split("Box::new(").nth(part).is_some_and(|argument| !argument.trim_start().starts_with("EmptyPart"))
+}
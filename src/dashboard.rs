@@ -0,0 +1,310 @@
+//! Full-screen terminal calendar of all days, drawn with plain ANSI escape sequences.
+//!
+//! Raw keyboard input is enabled through `stty`, so this only works on a unix-like terminal.
+
+use std::io::{Error, Read, stdin, stdout, Write};
+use std::panic;
+use std::panic::PanicHookInfo;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+
+use colored::{ColoredString, Colorize};
+
+use crate::harness::{Context, DayReport, DayRunner, PartReport, TestOutcome};
+
+const COLUMNS: usize = 5;
+const CELL_WIDTH: usize = 14;
+
+const HELP: &str = "←↑↓→/hjkl select   enter/r run   a run all   s sort   q/esc quit";
+
+pub fn dashboard(days: &[DayRunner], context: &Context) -> Result<(), Error> {
+    let terminal = Terminal::enter()?;
+    let result = Dashboard::new(days, context).event_loop();
+    terminal.leave()?;
+    result
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Order {
+    Calendar,
+    Runtime,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Run,
+    RunAll,
+    Sort,
+    Quit,
+    Other,
+}
+
+struct Dashboard<'a> {
+    days: &'a [DayRunner],
+    context: &'a Context,
+    reports: Vec<Option<DayReport>>,
+    selected: usize,
+    order: Order,
+    status: String,
+}
+
+impl<'a> Dashboard<'a> {
+    pub fn new(days: &'a [DayRunner], context: &'a Context) -> Self {
+        Self { days, context, reports: vec![None; days.len()], selected: 0, order: Order::Calendar, status: String::new() }
+    }
+
+    fn event_loop(&mut self) -> Result<(), Error> {
+        let mut input = stdin().lock();
+
+        loop {
+            self.draw()?;
+
+            match read_key(&mut input)? {
+                Key::Up if self.selected >= COLUMNS => self.selected -= COLUMNS,
+                Key::Down if self.selected + COLUMNS < self.days.len() => self.selected += COLUMNS,
+                Key::Left if self.selected > 0 => self.selected -= 1,
+                Key::Right if self.selected + 1 < self.days.len() => self.selected += 1,
+                Key::Run => self.run(self.ordered()[self.selected])?,
+                Key::RunAll => {
+                    for index in 0..self.days.len() {
+                        self.run(index)?;
+                    }
+                }
+                Key::Sort => self.toggle_order(),
+                Key::Quit => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+
+    fn run(&mut self, index: usize) -> Result<(), Error> {
+        let id = self.days[index].id;
+        self.status = format!("Running Day{:0>2}...", id);
+        self.draw()?;
+        let report = (self.days[index].report)(self.context);
+        self.status = format!("Day{:0>2} finished in {:.1?}", id, report.duration());
+        self.reports[index] = Some(report);
+        Ok(())
+    }
+
+    fn toggle_order(&mut self) {
+        let selected_day = self.ordered()[self.selected];
+        self.order = match self.order {
+            Order::Calendar => Order::Runtime,
+            Order::Runtime => Order::Calendar,
+        };
+        self.selected = self.ordered().iter().position(|&index| index == selected_day).unwrap();
+    }
+
+    /// Indices into `days` in the order they are laid out on the grid.
+    fn ordered(&self) -> Vec<usize> {
+        let mut result = (0..self.days.len()).collect::<Vec<_>>();
+        if self.order == Order::Runtime {
+            result.sort_by_key(|&index| std::cmp::Reverse(self.reports[index].as_ref().map(|r| r.duration())));
+        }
+        result
+    }
+
+    fn draw(&self) -> Result<(), Error> {
+        let mut frame = String::new();
+        frame.push_str("\x1b[H\x1b[2J");
+        frame.push_str(&format!("{}   sorted by {}   seed {}\n\n", "Advent of Code 2022".yellow(), match self.order {
+            Order::Calendar => "day",
+            Order::Runtime => "runtime",
+        }, self.context.seed));
+
+        let ordered = self.ordered();
+        for (row, chunk) in ordered.chunks(COLUMNS).enumerate() {
+            let mut titles = String::new();
+            let mut details = String::new();
+            for (column, &index) in chunk.iter().enumerate() {
+                let (title, detail) = self.cell(index, row * COLUMNS + column == self.selected);
+                titles.push_str(&title);
+                details.push_str(&detail);
+            }
+            frame.push_str(&format!("{}\n{}\n\n", titles, details));
+        }
+
+        frame.push_str(&self.detail(ordered[self.selected]));
+        frame.push_str(&format!("\n{}\n{}", self.status.purple(), HELP.dimmed()));
+
+        let mut out = stdout();
+        out.write_all(frame.replace('\n', "\r\n").as_bytes())?;
+        out.flush()
+    }
+
+    /// Both lines of a grid cell, each exactly `CELL_WIDTH` columns wide.
+    fn cell(&self, index: usize, selected: bool) -> (String, String) {
+        let title = format!(" Day{:0>2} ", self.days[index].id);
+        let title = if selected { title.reversed() } else { title.yellow() };
+        match &self.reports[index] {
+            Some(report) => (
+                format!("{}  {} {}  ", title, status_symbol(&report.part1), status_symbol(&report.part2)),
+                format!("{:>12}  ", format_duration(report.duration())),
+            ),
+            None => (format!("{}  {} {}  ", title, "·".dimmed(), "·".dimmed()), " ".repeat(CELL_WIDTH)),
+        }
+    }
+
+    fn detail(&self, index: usize) -> String {
+        let id = self.days[index].id;
+        let report = match &self.reports[index] {
+            Some(report) => report,
+            None => return format!("{}\n  not run yet\n", format!("Day{:0>2}", id).yellow()),
+        };
+
+        let mut result = format!("{}\n", format!("Day{:0>2}", id).yellow());
+        for part in report.parts() {
+            let test = match &part.test {
+                TestOutcome::Passed => format!("{} {}", "passed".green(), format_duration(part.test_duration).purple()),
+                TestOutcome::Failed { expected, actual } => format!("{} expected {} but got {}", "failed".red(), expected, actual),
+                TestOutcome::NotImplemented => "not implemented".dimmed().to_string(),
+                TestOutcome::MissingInput => "missing input".yellow().to_string(),
+            };
+            result.push_str(&format!("  Part {} test    {}\n", part.id, test));
+
+            match &part.answer {
+                Some(answer) if answer.lines().count() > 1 => {
+                    result.push_str(&format!("  Part {} output  {}\n", part.id, format_duration(part.duration).purple()));
+                    for line in answer.lines() {
+                        result.push_str(&format!("    {}\n", line.blue()));
+                    }
+                }
+                Some(answer) => result.push_str(&format!("  Part {} output  {} {}\n", part.id, answer.blue(), format_duration(part.duration).purple())),
+                None if part.test == TestOutcome::NotImplemented => result.push_str(&format!("  Part {} output  {}\n", part.id, "-".dimmed())),
+                None => result.push_str(&format!("  Part {} output  {}\n", part.id, "missing input".yellow())),
+            }
+        }
+        result
+    }
+}
+
+fn status_symbol(part: &PartReport) -> ColoredString {
+    match part.test {
+        TestOutcome::Passed => "✓".green(),
+        TestOutcome::Failed { .. } => "✗".red(),
+        TestOutcome::NotImplemented => "·".dimmed(),
+        TestOutcome::MissingInput => "?".yellow(),
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.1?}", duration)
+}
+
+/// Reads the next byte, or `None` once the terminal has had nothing to read for a tenth of a second.
+fn read_byte(input: &mut impl Read) -> Result<Option<u8>, Error> {
+    let mut byte = [0];
+    match input.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+fn read_key(input: &mut impl Read) -> Result<Key, Error> {
+    let first = loop {
+        if let Some(byte) = read_byte(input)? {
+            break byte;
+        }
+    };
+
+    let key = match first {
+        // Arrow keys arrive as escape sequences right away, so an escape on its own is the Esc key
+        0x1b => match read_byte(input)? {
+            None => Key::Quit,
+            Some(b'[') => match read_byte(input)? {
+                Some(b'A') => Key::Up,
+                Some(b'B') => Key::Down,
+                Some(b'C') => Key::Right,
+                Some(b'D') => Key::Left,
+                _ => Key::Other,
+            },
+            Some(_) => Key::Other,
+        },
+        b'k' => Key::Up,
+        b'j' => Key::Down,
+        b'l' => Key::Right,
+        b'h' => Key::Left,
+        b'\n' | b'\r' | b'r' => Key::Run,
+        b'a' => Key::RunAll,
+        b's' => Key::Sort,
+        b'q' => Key::Quit,
+        _ => Key::Other,
+    };
+
+    Ok(key)
+}
+
+type PanicHook = Box<dyn Fn(&PanicHookInfo<'_>) + Sync + Send + 'static>;
+
+struct Terminal {
+    saved_settings: String,
+    /// The panic hook installed before ours, which gets it back on leaving.
+    previous_hook: Arc<PanicHook>,
+}
+
+impl Terminal {
+    fn enter() -> Result<Self, Error> {
+        let saved_settings = stty(&["-g"])?.trim().to_string();
+        // Reads give up after a tenth of a second without input, which tells a lone Esc from an escape sequence
+        stty(&["-icanon", "-echo", "min", "0", "time", "1"])?;
+
+        let restore_settings = saved_settings.clone();
+        let previous_hook = Arc::new(panic::take_hook());
+        let chained_hook = previous_hook.clone();
+        panic::set_hook(Box::new(move |info| {
+            let _ = Self::restore(&restore_settings);
+            chained_hook(info);
+        }));
+
+        print!("\x1b[?1049h\x1b[?25l");
+        stdout().flush()?;
+
+        Ok(Self { saved_settings, previous_hook })
+    }
+
+    fn leave(self) -> Result<(), Error> {
+        // Dropping our hook releases its share of the previous one
+        drop(panic::take_hook());
+        match Arc::try_unwrap(self.previous_hook) {
+            Ok(previous_hook) => panic::set_hook(previous_hook),
+            Err(previous_hook) => panic::set_hook(Box::new(move |info| previous_hook(info))),
+        }
+        Self::restore(&self.saved_settings)
+    }
+
+    fn restore(saved_settings: &str) -> Result<(), Error> {
+        print!("\x1b[?25h\x1b[?1049l");
+        stdout().flush()?;
+        stty(&[saved_settings]).map(|_| ())
+    }
+}
+
+fn stty(args: &[&str]) -> Result<String, Error> {
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).output()?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_key, Key};
+
+    #[test]
+    fn reads_arrows_and_a_lone_escape() {
+        // An exhausted slice reads nothing, like a terminal with no further input
+        let mut input: &[u8] = b"\x1b[Cj\x1b[Z\x1b";
+
+        let keys = (0..4).map(|_| read_key(&mut input).unwrap()).collect::<Vec<_>>();
+        assert_eq!(keys, vec![Key::Right, Key::Down, Key::Other, Key::Quit]);
+    }
+}
@@ -1,53 +1,197 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind};
+use std::num::ParseIntError;
+
 use crate::harness::{Day, Part};
 
-pub fn day01() -> Day<u32, u32> {
+pub fn day01() -> Day<u64, u64> {
     Day::new(1, Box::new(Part1 {}), Box::new(Part2 {}))
 }
 
 pub struct Part1;
 
-impl Part<u32> for Part1 {
-    fn expect_test(&self) -> u32 {
+impl Part<u64> for Part1 {
+    fn expect_test(&self) -> u64 {
         24000
     }
 
-    fn solve(&self, input: &Vec<String>) -> u32 {
-        parse_elves(input).into_iter().max().unwrap()
+    fn solve(&self, input: &Vec<String>) -> u64 {
+        count_calories(input, 1).top_total()
     }
 }
 
 pub struct Part2;
 
-impl Part<u32> for Part2 {
-    fn expect_test(&self) -> u32 {
+impl Part<u64> for Part2 {
+    fn expect_test(&self) -> u64 {
         45000
     }
 
-    fn solve(&self, input: &Vec<String>) -> u32 {
-        let mut vec = parse_elves(input);
-        vec.sort();
-        vec.reverse();
-        vec[0..3].iter().sum::<u32>()
+    fn solve(&self, input: &Vec<String>) -> u64 {
+        count_calories(input, 3).top_total()
+    }
+}
+
+fn count_calories(input: &[String], k: usize) -> Summary {
+    CalorieCounter::from_reader(input.join("\n").as_bytes(), k).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Prints the `k` elves carrying the most calories in `input_path`, reading it line by line.
+pub fn report_calories(input_path: &str, k: usize) -> Result<(), Error> {
+    let summary = CalorieCounter::from_reader(BufReader::new(File::open(input_path)?), k)
+        .map_err(|e| Error::new(e.kind(), format!("{}: {}", input_path, e)))?;
+    println!("{}", summary);
+    Ok(())
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+struct Elf {
+    total: u64,
+    // Ties are broken in favour of the elf that came first
+    index: Reverse<usize>,
+    items: usize,
+}
+
+impl Elf {
+    pub fn index(&self) -> usize {
+        self.index.0
+    }
+}
+
+impl Display for Elf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Elf #{} carrying {} calories in {} items", self.index(), self.total, self.items)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Summary {
+    elves: usize,
+    items: usize,
+    total: u64,
+    /// The k elves carrying the most calories, most first
+    top: Vec<Elf>,
+}
+
+impl Summary {
+    pub fn top_total(&self) -> u64 {
+        self.top.iter().map(|elf| elf.total).sum()
     }
 }
 
-fn parse_elves(input: &Vec<String>) -> Vec<u32> {
-    let mut elves = Vec::new();
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} elves carrying {} calories in {} items", self.elves, self.total, self.items)?;
+        for elf in &self.top {
+            writeln!(f, "  {}", elf)?;
+        }
+        write!(f, "The top {} carry {} calories", self.top.len(), self.top_total())
+    }
+}
+
+/// Sums calories elf by elf while only ever keeping the `k` largest elves around.
+struct CalorieCounter {
+    k: usize,
+    top: BinaryHeap<Reverse<Elf>>,
+    current: Option<Elf>,
+    elves: usize,
+    items: usize,
+    total: u64,
+}
+
+impl CalorieCounter {
+    pub fn new(k: usize) -> Self {
+        Self { k, top: BinaryHeap::with_capacity(k + 1), current: None, elves: 0, items: 0, total: 0 }
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R, k: usize) -> Result<Summary, Error> {
+        let mut counter = Self::new(k);
+
+        for (i, line) in reader.lines().enumerate() {
+            counter.push_line(&line?).map_err(|e| Error::new(ErrorKind::InvalidData, format!("Line {}: {}", i + 1, e)))?;
+        }
+
+        Ok(counter.finish())
+    }
+
+    /// Adds an item to the current elf, or finishes the current elf on a blank line.
+    pub fn push_line(&mut self, line: &str) -> Result<(), ParseIntError> {
+        let line = line.trim();
+
+        if line.is_empty() {
+            self.finish_elf();
+            return Ok(());
+        }
+
+        let calories = line.parse::<u64>()?;
+        let index = self.elves;
+        let elf = self.current.get_or_insert(Elf { total: 0, index: Reverse(index), items: 0 });
+        elf.total += calories;
+        elf.items += 1;
+        self.items += 1;
+        self.total += calories;
+
+        Ok(())
+    }
 
-    let mut current_elf = 0_u32;
+    fn finish_elf(&mut self) {
+        if let Some(elf) = self.current.take() {
+            self.elves += 1;
+            self.top.push(Reverse(elf));
+            if self.top.len() > self.k {
+                self.top.pop();
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> Summary {
+        self.finish_elf();
 
-    for calories in input {
-        if let Ok(u) = calories.parse::<u32>() {
-            current_elf += u;
-        } else {
-            elves.push(current_elf);
-            current_elf = 0;
+        Summary {
+            elves: self.elves,
+            items: self.items,
+            total: self.total,
+            top: self.top.into_sorted_vec().into_iter().map(|Reverse(elf)| elf).collect(),
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CalorieCounter;
+
+    #[test]
+    fn keeps_every_elf_when_asked_for_more_than_there_are() {
+        let summary = CalorieCounter::from_reader("1000\n2000\n\n4000\n".as_bytes(), 5).unwrap();
+
+        assert_eq!((summary.elves, summary.items, summary.total), (2, 3, 7000));
+        assert_eq!(summary.top.iter().map(|elf| (elf.index(), elf.total)).collect::<Vec<_>>(), vec![(1, 4000), (0, 3000)]);
+        assert_eq!(summary.to_string(), [
+            "2 elves carrying 7000 calories in 3 items",
+            "  Elf #1 carrying 4000 calories in 1 items",
+            "  Elf #0 carrying 3000 calories in 2 items",
+            "The top 2 carry 7000 calories",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn breaks_ties_in_favour_of_the_first_elf() {
+        let summary = CalorieCounter::from_reader("300\n\n500\n\n200\n100\n\n500\n\n300".as_bytes(), 2).unwrap();
 
-    if current_elf != 0 {
-        elves.push(current_elf);
+        assert_eq!(summary.top.iter().map(|elf| elf.index()).collect::<Vec<_>>(), vec![1, 3]);
+
+        let summary = CalorieCounter::from_reader("300\n\n500\n\n200\n100\n\n500\n\n300".as_bytes(), 4).unwrap();
+        assert_eq!(summary.top.iter().map(|elf| elf.index()).collect::<Vec<_>>(), vec![1, 3, 0, 2]);
     }
 
-    elves
+    #[test]
+    fn reports_unparseable_lines_with_their_number() {
+        let error = CalorieCounter::from_reader("1000\n\n20x0\n".as_bytes(), 1).unwrap_err();
+
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "Line 3: invalid digit found in string");
+    }
 }
@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 use crate::harness::{Day, Part};
 
 pub fn day02() -> Day<u32, u32> {
@@ -6,38 +8,14 @@ pub fn day02() -> Day<u32, u32> {
 
 pub struct Part1;
 
-static LOSS_SCORE: u32 = 0;
-static DRAW_SCORE: u32 = 3;
-static WIN_SCORE: u32 = 6;
-
-static ROCK_SCORE: u32 = 1;
-static PAPER_SCORE: u32 = 2;
-static SCISSORS_SCORE: u32 = 3;
-
 impl Part<u32> for Part1 {
     fn expect_test(&self) -> u32 {
         15
     }
 
     fn solve(&self, input: &Vec<String>) -> u32 {
-        sum(input, Self::score)
-    }
-}
-
-impl Part1 {
-    fn score(a: char, b: char) -> u32 {
-        match (a, b) {
-            ('C', 'A') => ROCK_SCORE + WIN_SCORE,
-            ('A', 'A') => ROCK_SCORE + DRAW_SCORE,
-            ('B', 'A') => ROCK_SCORE + LOSS_SCORE,
-            ('A', 'B') => PAPER_SCORE + WIN_SCORE,
-            ('B', 'B') => PAPER_SCORE + DRAW_SCORE,
-            ('C', 'B') => PAPER_SCORE + LOSS_SCORE,
-            ('B', 'C') => SCISSORS_SCORE + WIN_SCORE,
-            ('C', 'C') => SCISSORS_SCORE + DRAW_SCORE,
-            ('A', 'C') => SCISSORS_SCORE + LOSS_SCORE,
-            (_, _) => panic!(),
-        }
+        let game = Game::rock_paper_scissors();
+        Guide::parse(input, &game).unwrap_or_else(|e| panic!("{}", e)).score_as_shapes(&game)
     }
 }
 
@@ -49,32 +27,331 @@ impl Part<u32> for Part2 {
     }
 
     fn solve(&self, input: &Vec<String>) -> u32 {
-        sum(input, Self::score)
+        let game = Game::rock_paper_scissors();
+        Guide::parse(input, &game).and_then(|guide| guide.score_as_outcomes(&game)).unwrap_or_else(|e| panic!("{}", e))
     }
 }
 
-impl Part2 {
-    fn score(a: char, b: char) -> u32 {
-        match (a, b) {
-            ('C', 'C') => ROCK_SCORE + WIN_SCORE,
-            ('A', 'B') => ROCK_SCORE + DRAW_SCORE,
-            ('B', 'A') => ROCK_SCORE + LOSS_SCORE,
-            ('A', 'C') => PAPER_SCORE + WIN_SCORE,
-            ('B', 'B') => PAPER_SCORE + DRAW_SCORE,
-            ('C', 'A') => PAPER_SCORE + LOSS_SCORE,
-            ('B', 'C') => SCISSORS_SCORE + WIN_SCORE,
-            ('C', 'B') => SCISSORS_SCORE + DRAW_SCORE,
-            ('A', 'A') => SCISSORS_SCORE + LOSS_SCORE,
-            (_, _) => panic!(),
+const ROCK_PAPER_SCISSORS: &str = "
+Rock 1 beats Scissors
+Paper 2 beats Rock
+Scissors 3 beats Paper
+";
+
+const ROCK_PAPER_SCISSORS_LIZARD_SPOCK: &str = "
+Rock 1 beats Scissors Lizard
+Paper 2 beats Rock Spock
+Scissors 3 beats Paper Lizard
+Lizard 4 beats Spock Paper
+Spock 5 beats Scissors Rock
+";
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Outcome {
+    Loss,
+    Draw,
+    Win,
+}
+
+impl Outcome {
+    const ALL: [Outcome; 3] = [Outcome::Loss, Outcome::Draw, Outcome::Win];
+
+    fn score(&self) -> u32 {
+        match self {
+            Outcome::Loss => 0,
+            Outcome::Draw => 3,
+            Outcome::Win => 6,
         }
     }
 }
 
-fn sum<FScore: Fn(char, char) -> u32>(input: &Vec<String>, calculate_score: FScore) -> u32 {
-    input
-        .into_iter()
-        .filter(|line| !line.is_empty())
-        .map(|line| line.as_bytes())
-        .map(|line| calculate_score(line[0] as char, (line[2] - 'X' as u8 + 'A' as u8) as char))
-        .sum()
+#[derive(Debug, Clone)]
+struct Shape {
+    name: String,
+    score: u32,
+}
+
+/// A set of shapes and which of them beats which, parsed from lines like `Rock 1 beats Scissors Lizard`.
+#[derive(Debug, Clone)]
+struct Game {
+    shapes: Vec<Shape>,
+    beats: Vec<Vec<bool>>,
+}
+
+impl Game {
+    pub fn rock_paper_scissors() -> Self {
+        ROCK_PAPER_SCISSORS.parse().unwrap()
+    }
+
+    pub fn rock_paper_scissors_lizard_spock() -> Self {
+        ROCK_PAPER_SCISSORS_LIZARD_SPOCK.parse().unwrap()
+    }
+
+    fn len(&self) -> usize {
+        self.shapes.len()
+    }
+
+    fn outcome(&self, ours: usize, theirs: usize) -> Outcome {
+        if self.beats[ours][theirs] {
+            Outcome::Win
+        } else if self.beats[theirs][ours] {
+            Outcome::Loss
+        } else {
+            Outcome::Draw
+        }
+    }
+
+    fn score(&self, ours: usize, theirs: usize) -> u32 {
+        self.shapes[ours].score + self.outcome(ours, theirs).score()
+    }
+
+    /// The highest scoring shape to play against `theirs` to get the wanted outcome.
+    fn response(&self, theirs: usize, outcome: Outcome) -> Option<usize> {
+        (0..self.len())
+            .filter(|&ours| self.outcome(ours, theirs) == outcome)
+            .max_by_key(|&ours| self.shapes[ours].score)
+    }
+}
+
+impl FromStr for Game {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rules = s.lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| match &line.split_whitespace().collect::<Vec<_>>()[..] {
+                [name, score, "beats", beaten @ ..] => {
+                    let score = score.parse::<u32>().map_err(|e| format!("Invalid score in \"{}\": {}", line, e))?;
+                    Ok((Shape { name: name.to_string(), score }, beaten.to_vec()))
+                }
+                _ => Err(format!("Expected \"<shape> <score> beats <shape>...\" but got \"{}\"", line)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let shapes = rules.iter().map(|(shape, _)| shape.clone()).collect::<Vec<_>>();
+        let index_of = |name: &str| shapes.iter().position(|shape| shape.name == name).ok_or(format!("Unknown shape {}", name));
+
+        let mut beats = vec![vec![false; shapes.len()]; shapes.len()];
+        for (winner, (shape, beaten)) in rules.iter().enumerate() {
+            for name in beaten {
+                let loser = index_of(name)?;
+                if winner == loser {
+                    return Err(format!("{} can not beat itself", shape.name));
+                }
+                beats[winner][loser] = true;
+            }
+        }
+
+        for winner in 0..shapes.len() {
+            for loser in 0..shapes.len() {
+                if beats[winner][loser] && beats[loser][winner] {
+                    return Err(format!("{} and {} beat each other", shapes[winner].name, shapes[loser].name));
+                }
+            }
+        }
+
+        Ok(Game { shapes, beats })
+    }
+}
+
+/// The strategy guide as letter indices: the opponent's column counts up from `A`,
+/// the response column counts down from `Z`, so `X Y Z` for three shapes and `V W X Y Z` for five.
+#[derive(Debug, Clone)]
+struct Guide {
+    rounds: Vec<(usize, usize)>,
+}
+
+impl Guide {
+    pub fn parse(input: &[String], game: &Game) -> Result<Self, String> {
+        if game.len() > 26 {
+            return Err(format!("A guide can only name 26 shapes but the game has {}", game.len()));
+        }
+        let n = game.len() as u8;
+        let first_response = b'Z' + 1 - n;
+
+        let rounds = input.iter()
+            .filter(|line| !line.is_empty())
+            .map(|line| match line.as_bytes() {
+                &[theirs, b' ', response] if (b'A'..b'A' + n).contains(&theirs) && (first_response..=b'Z').contains(&response) =>
+                    Ok(((theirs - b'A') as usize, (response - first_response) as usize)),
+                _ => Err(format!("Invalid round \"{}\"", line)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Guide { rounds })
+    }
+
+    /// Total score when the response column names the shape to play.
+    pub fn score_as_shapes(&self, game: &Game) -> u32 {
+        self.score_with_mapping(game, &(0..game.len()).collect::<Vec<_>>())
+    }
+
+    /// Total score when the response column names the outcome, `X` to lose, `Y` to draw and `Z` to win.
+    pub fn score_as_outcomes(&self, game: &Game) -> Result<u32, String> {
+        self.rounds.iter()
+            .map(|&(theirs, response)| {
+                let outcome = (response + Outcome::ALL.len()).checked_sub(game.len()).and_then(|i| Outcome::ALL.get(i).copied())
+                    .ok_or_else(|| format!("Only X, Y and Z name outcomes but a round answers {}", (b'Z' + 1 - game.len() as u8 + response as u8) as char))?;
+                let ours = game.response(theirs, outcome).ok_or_else(|| format!("Nothing gives {:?} against {}", outcome, game.shapes[theirs].name))?;
+                Ok(game.score(ours, theirs))
+            })
+            .sum()
+    }
+
+    fn score_with_mapping(&self, game: &Game, mapping: &[usize]) -> u32 {
+        self.rounds.iter().map(|&(theirs, response)| game.score(mapping[response], theirs)).sum()
+    }
+
+    /// Lowest and highest total score over every way of reading the response column as shapes. Each response
+    /// contributes on its own once its shape is fixed, so both are assignment problems rather than a search over every reading.
+    pub fn score_range(&self, game: &Game) -> (u32, u32) {
+        // Total score of every response when read as every shape
+        let mut totals = vec![vec![0; game.len()]; game.len()];
+        for &(theirs, response) in &self.rounds {
+            for (shape, total) in totals[response].iter_mut().enumerate() {
+                *total += game.score(shape, theirs) as i64;
+            }
+        }
+
+        let negated = totals.iter().map(|row| row.iter().map(|total| -total).collect()).collect::<Vec<_>>();
+        (min_assignment(&totals) as u32, -min_assignment(&negated) as u32)
+    }
+}
+
+/// Lowest total cost of giving every row its own column of a square matrix, with the Hungarian algorithm in O(n³).
+fn min_assignment(cost: &[Vec<i64>]) -> i64 {
+    let n = cost.len();
+    // Potentials of rows and columns, and the row assigned to each column, all shifted by one so 0 can be a sentinel column
+    let (mut row_potential, mut column_potential) = (vec![0; n + 1], vec![0; n + 1]);
+    let mut assigned = vec![0; n + 1];
+    let mut way = vec![0; n + 1];
+
+    for row in 1..=n {
+        assigned[0] = row;
+        let mut column = 0;
+        let mut slack = vec![i64::MAX; n + 1];
+        let mut used = vec![false; n + 1];
+
+        while assigned[column] != 0 {
+            used[column] = true;
+            let current_row = assigned[column];
+            let (mut delta, mut next_column) = (i64::MAX, 0);
+
+            for j in (1..=n).filter(|&j| !used[j]) {
+                let reduced = cost[current_row - 1][j - 1] - row_potential[current_row] - column_potential[j];
+                if reduced < slack[j] {
+                    slack[j] = reduced;
+                    way[j] = column;
+                }
+                if slack[j] < delta {
+                    delta = slack[j];
+                    next_column = j;
+                }
+            }
+
+            for j in 0..=n {
+                if used[j] {
+                    row_potential[assigned[j]] += delta;
+                    column_potential[j] -= delta;
+                } else {
+                    slack[j] -= delta;
+                }
+            }
+            column = next_column;
+        }
+
+        while column != 0 {
+            let previous = way[column];
+            assigned[column] = assigned[previous];
+            column = previous;
+        }
+    }
+
+    (1..=n).map(|j| cost[assigned[j] - 1][j - 1]).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::{min_assignment, Game, Guide};
+
+    fn permutations(n: usize) -> Vec<Vec<usize>> {
+        if n == 0 {
+            return vec![vec![]];
+        }
+
+        permutations(n - 1)
+            .into_iter()
+            .flat_map(|permutation| (0..n).map(move |i| {
+                let mut result = permutation.clone();
+                result.insert(i, n - 1);
+                result
+            }))
+            .collect()
+    }
+
+    #[test]
+    fn assignment_matches_trying_every_permutation() {
+        let mut rng = StdRng::seed_from_u64(2);
+
+        for _ in 0..300 {
+            let n = rng.gen_range(0..=6);
+            let cost = (0..n).map(|_| (0..n).map(|_| rng.gen_range(-50..50)).collect()).collect::<Vec<Vec<i64>>>();
+            let brute_force = permutations(n).iter().map(|columns| columns.iter().enumerate().map(|(row, &column)| cost[row][column]).sum()).min().unwrap();
+            assert_eq!(min_assignment(&cost), brute_force, "{:?}", cost);
+        }
+    }
+
+    fn guide(rounds: &[&str], game: &Game) -> Guide {
+        Guide::parse(&rounds.iter().map(|round| round.to_string()).collect::<Vec<_>>(), game).unwrap()
+    }
+
+    #[test]
+    fn scores_the_example_every_way() {
+        let game = Game::rock_paper_scissors();
+        let guide = guide(&["A Y", "B X", "C Z"], &game);
+
+        assert_eq!(guide.score_as_shapes(&game), 15);
+        assert_eq!(guide.score_as_outcomes(&game), Ok(12));
+        assert_eq!(guide.score_range(&game), (6, 24));
+    }
+
+    #[test]
+    fn scores_rock_paper_scissors_lizard_spock() {
+        let game = Game::rock_paper_scissors_lizard_spock();
+
+        let shapes = guide(&["A V", "B W", "C Z", "D X", "E Y"], &game);
+        assert_eq!(shapes.score_as_shapes(&game), 4 + 5 + 11 + 9 + 10);
+        assert_eq!(shapes.score_range(&game), (15, 45));
+
+        let outcomes = guide(&["A X", "B Y", "C Z", "D Z", "E X"], &game);
+        assert_eq!(outcomes.score_as_outcomes(&game), Ok(4 + 5 + 11 + 9 + 3));
+        assert_eq!(shapes.score_as_outcomes(&game), Err("Only X, Y and Z name outcomes but a round answers V".to_string()));
+    }
+
+    #[test]
+    fn rejects_guides_that_letters_can_not_describe() {
+        let game = Game::rock_paper_scissors();
+        assert!(Guide::parse(&["D X".to_string()], &game).is_err());
+        assert!(Guide::parse(&["A W".to_string()], &game).is_err());
+
+        let rules = (0..27).map(|i| format!("Shape{} {} beats", i, i + 1)).collect::<Vec<_>>().join("\n");
+        let game = rules.parse::<Game>().unwrap();
+        assert_eq!(Guide::parse(&["A Z".to_string()], &game).unwrap_err(), "A guide can only name 26 shapes but the game has 27");
+    }
+
+    #[test]
+    fn scores_the_range_of_large_games_quickly() {
+        let rules = (0..26).map(|i| format!("S{} {} beats S{}", i, i + 1, (i + 1) % 26)).collect::<Vec<_>>().join("\n");
+        let game = rules.parse::<Game>().unwrap();
+        let rounds = (0..26u8).map(|i| format!("{} {}", (b'A' + i) as char, (b'A' + (i + 1) % 26) as char)).collect::<Vec<_>>();
+        let guide = Guide::parse(&rounds, &game).unwrap();
+
+        // Reading every response as the shape it beats wins all 26 rounds, and as the one it loses to loses them all
+        let shape_scores = (1..=26).sum::<u32>();
+        assert_eq!(guide.score_range(&game), (shape_scores, shape_scores + 26 * 6));
+    }
 }
@@ -1,4 +1,5 @@
-use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::ops::{BitAnd, BitOr, BitXor, Range};
 
 use crate::harness::{Day, Part};
 
@@ -14,46 +15,284 @@ impl Part<u32> for Part1 {
     }
 
     fn solve(&self, input: &Vec<String>) -> u32 {
-        input.iter().filter(|line| !line.is_empty()).map(part1).sum()
+        parse(input).unwrap().iter().map(|rucksack| rucksack.misplaced().priority_sum()).sum()
     }
 }
 
-fn part1(p0: &String) -> u32 {
-    let compartments: Vec<HashSet<char>> =
-        p0
-            .chars()
-            .collect::<Vec<_>>()
-            .chunks(p0.len() / 2)
-            .map(|arr| HashSet::from_iter(arr.iter().cloned()))
-            .collect::<Vec<_>>();
+pub struct Part2;
+
+impl Part<u32> for Part2 {
+    fn expect_test(&self) -> u32 {
+        70
+    }
 
-    compartments[0].intersection(&compartments[1]).map(|c| score(*c)).sum()
+    fn solve(&self, input: &Vec<String>) -> u32 {
+        let rucksacks = parse(input).unwrap();
+
+        group(&rucksacks, &Grouping::Size(3))
+            .unwrap_or_else(|e| panic!("{}", e))
+            .iter()
+            .map(|group| group.badge().unwrap_or_else(|e| panic!("{}", e)))
+            .map(|badge| priority(badge).unwrap())
+            .sum()
+    }
 }
 
-fn part2(p0: &[String]) -> u32 {
-    let rucksacks = p0.iter().map(|arr| arr.chars().collect::<Vec<char>>()).map(|chars: Vec<char>| -> HashSet<char> { HashSet::from_iter(chars.iter().cloned()) }).collect::<Vec<_>>();
+fn parse(input: &[String]) -> Result<Vec<Rucksack>, String> {
+    input.iter()
+        .filter(|line| !line.is_empty())
+        .enumerate()
+        .map(|(i, line)| Rucksack::parse(line).map_err(|e| format!("Rucksack {}: {}", i + 1, e)))
+        .collect()
+}
 
-    let x = rucksacks.iter().flatten().collect::<HashSet<_>>();
+fn priority(item: char) -> Option<u32> {
+    match item {
+        'a'..='z' => Some(item as u32 - 'a' as u32 + 1),
+        'A'..='Z' => Some(item as u32 - 'A' as u32 + 27),
+        _ => None,
+    }
+}
 
-    x.into_iter().filter(|c| rucksacks.iter().all(|set| set.contains(c))).map(|c| score(*c)).sum()
+fn item(priority: u32) -> char {
+    match priority {
+        1..=26 => char::from_u32('a' as u32 + priority - 1).unwrap(),
+        27..=52 => char::from_u32('A' as u32 + priority - 27).unwrap(),
+        _ => panic!("There is no item with priority {}", priority),
+    }
 }
 
-fn score(c: char) -> u32 {
-    if c.is_lowercase() {
-        c as u32 - 'a' as u32 + 1
-    } else {
-        c as u32 - 'A' as u32 + 27
+/// Set of items with bit `n` standing for the item of priority `n`.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+struct ItemSet(u64);
+
+impl ItemSet {
+    pub fn parse(items: &str) -> Result<Self, String> {
+        items.chars()
+            .map(|c| priority(c).map(|p| ItemSet(1 << p)).ok_or(format!("{:?} is not an item", c)))
+            .try_fold(ItemSet::default(), |acc, item| item.map(|item| acc | item))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn len(&self) -> u32 {
+        self.0.count_ones()
+    }
+
+    pub fn contains(&self, item: char) -> bool {
+        priority(item).is_some_and(|p| self.0 & 1 << p != 0)
+    }
+
+    pub fn priorities(&self) -> impl Iterator<Item=u32> + '_ {
+        (1..=52).filter(|p| self.0 & 1 << p != 0)
+    }
+
+    pub fn items(&self) -> impl Iterator<Item=char> + '_ {
+        self.priorities().map(item)
+    }
+
+    pub fn priority_sum(&self) -> u32 {
+        self.priorities().sum()
+    }
+
+    pub fn intersection(self, other: Self) -> Self {
+        self & other
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        self | other
+    }
+
+    pub fn symmetric_difference(self, other: Self) -> Self {
+        self ^ other
     }
 }
 
-pub struct Part2;
+impl BitAnd for ItemSet {
+    type Output = Self;
 
-impl Part<u32> for Part2 {
-    fn expect_test(&self) -> u32 {
-        70
+    fn bitand(self, rhs: Self) -> Self::Output {
+        ItemSet(self.0 & rhs.0)
     }
+}
 
-    fn solve(&self, input: &Vec<String>) -> u32 {
-        input.chunks(3).collect::<Vec<_>>().into_iter().map(|x| part2(x)).sum()
+impl BitOr for ItemSet {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        ItemSet(self.0 | rhs.0)
+    }
+}
+
+impl BitXor for ItemSet {
+    type Output = Self;
+
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        ItemSet(self.0 ^ rhs.0)
+    }
+}
+
+impl Display for ItemSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.items().collect::<String>())
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Rucksack {
+    first: ItemSet,
+    second: ItemSet,
+}
+
+impl Rucksack {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        // Items are single bytes once every character is known to be one
+        if let Some(c) = line.chars().find(|&c| priority(c).is_none()) {
+            return Err(format!("{:?} is not an item", c));
+        }
+        if !line.len().is_multiple_of(2) {
+            return Err(format!("{} items can not be split evenly between two compartments", line.len()));
+        }
+
+        let (first, second) = line.split_at(line.len() / 2);
+        Ok(Rucksack { first: ItemSet::parse(first)?, second: ItemSet::parse(second)? })
+    }
+
+    pub fn items(&self) -> ItemSet {
+        self.first | self.second
+    }
+
+    /// Items that ended up in both compartments.
+    pub fn misplaced(&self) -> ItemSet {
+        self.first & self.second
+    }
+}
+
+/// Whether two consecutive rucksacks belong to the same group.
+type SameGroup = Box<dyn Fn(&Rucksack, &Rucksack) -> bool>;
+
+enum Grouping {
+    /// Consecutive groups of exactly this many rucksacks.
+    Size(usize),
+    /// Puts the next rucksack into the current group as long as this returns true for the previous and the next one.
+    Predicate(SameGroup),
+}
+
+#[derive(Debug, Clone)]
+struct Group {
+    /// Indices of the group's rucksacks in the input
+    rucksacks: Range<usize>,
+    shared: ItemSet,
+}
+
+impl Group {
+    pub fn new(rucksacks: Range<usize>, members: &[Rucksack]) -> Self {
+        let shared = members.iter().map(|r| r.items()).reduce(ItemSet::intersection).unwrap_or_default();
+        Self { rucksacks, shared }
+    }
+
+    /// The one item every elf of the group carries.
+    pub fn badge(&self) -> Result<char, BadgeError> {
+        match self.shared.len() {
+            1 => Ok(self.shared.items().next().unwrap()),
+            0 => Err(BadgeError::NoSharedItem(self.rucksacks.clone())),
+            _ => Err(BadgeError::Ambiguous(self.rucksacks.clone(), self.shared)),
+        }
+    }
+}
+
+fn group(rucksacks: &[Rucksack], grouping: &Grouping) -> Result<Vec<Group>, String> {
+    let mut bounds = vec![0];
+
+    match grouping {
+        Grouping::Size(size) => {
+            assert!(*size > 0, "Groups need at least one rucksack");
+            if !rucksacks.len().is_multiple_of(*size) {
+                let complete = rucksacks.len() - rucksacks.len() % size;
+                return Err(format!("Rucksacks {} to {} make an incomplete group of {} out of {}", complete + 1, rucksacks.len(), rucksacks.len() - complete, size));
+            }
+            bounds.extend((1..=rucksacks.len() / size).map(|i| i * size))
+        }
+        Grouping::Predicate(same_group) => bounds.extend((1..rucksacks.len()).filter(|&i| !same_group(&rucksacks[i - 1], &rucksacks[i]))),
+    }
+
+    if *bounds.last().unwrap() != rucksacks.len() {
+        bounds.push(rucksacks.len());
+    }
+
+    Ok(bounds.windows(2)
+        .map(|w| Group::new(w[0]..w[1], &rucksacks[w[0]..w[1]]))
+        .collect())
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum BadgeError {
+    NoSharedItem(Range<usize>),
+    Ambiguous(Range<usize>, ItemSet),
+}
+
+impl Display for BadgeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BadgeError::NoSharedItem(rucksacks) => write!(f, "Rucksacks {} to {} have no item in common", rucksacks.start + 1, rucksacks.end),
+            BadgeError::Ambiguous(rucksacks, shared) => write!(f, "Rucksacks {} to {} share more than one item: {}", rucksacks.start + 1, rucksacks.end, shared),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{group, priority, BadgeError, Grouping, ItemSet, Rucksack};
+
+    #[test]
+    fn rejects_rucksacks_with_unknown_items() {
+        assert_eq!(Rucksack::parse("aéb").unwrap_err(), "'é' is not an item");
+        assert_eq!(Rucksack::parse("abc").unwrap_err(), "3 items can not be split evenly between two compartments");
+    }
+
+    #[test]
+    fn rejects_incomplete_groups() {
+        let rucksacks = ["abca", "bcab", "cabc", "bchj"].map(|line| Rucksack::parse(line).unwrap());
+
+        assert_eq!(group(&rucksacks, &Grouping::Size(3)).unwrap_err(), "Rucksacks 4 to 4 make an incomplete group of 1 out of 3");
+        assert_eq!(group(&rucksacks, &Grouping::Size(2)).map(|groups| groups.len()), Ok(2));
+    }
+
+    #[test]
+    fn intersects_item_sets_by_priority() {
+        let first = ItemSet::parse("vJrwpWtwJgWr").unwrap();
+        let second = ItemSet::parse("hcsFMMfFFhFp").unwrap();
+
+        assert_eq!(first.intersection(second).to_string(), "p");
+        assert_eq!(first.intersection(second).priority_sum(), 16);
+        assert_eq!(ItemSet::parse("aAzZ").unwrap().priorities().collect::<Vec<_>>(), vec![1, 26, 27, 52]);
+        assert_eq!((priority('L'), priority('P'), priority('1')), (Some(38), Some(42), None));
+        assert!(first.intersection(ItemSet::parse("xyz").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn groups_rucksacks_by_predicate() {
+        let rucksacks = ["aXab", "cdXc", "efYe", "fYef", "gZgh"].map(|line| Rucksack::parse(line).unwrap());
+        // Rucksacks stay together while they share an uppercase item
+        let grouping = Grouping::Predicate(Box::new(|previous: &Rucksack, next: &Rucksack| {
+            previous.items().intersection(next.items()).priorities().any(|p| p > 26)
+        }));
+
+        let groups = group(&rucksacks, &grouping).unwrap();
+        assert_eq!(groups.iter().map(|g| g.rucksacks.clone()).collect::<Vec<_>>(), vec![0..2, 2..4, 4..5]);
+        assert_eq!(groups[0].badge(), Ok('X'));
+        assert_eq!(groups[1].badge(), Err(BadgeError::Ambiguous(2..4, ItemSet::parse("efY").unwrap())));
+        assert_eq!(groups[1].badge().unwrap_err().to_string(), "Rucksacks 3 to 4 share more than one item: efY");
+    }
+
+    #[test]
+    fn reports_groups_without_a_shared_item() {
+        let rucksacks = ["abab", "cdcd"].map(|line| Rucksack::parse(line).unwrap());
+
+        let groups = group(&rucksacks, &Grouping::Size(2)).unwrap();
+        assert_eq!(groups[0].badge(), Err(BadgeError::NoSharedItem(0..2)));
+        assert_eq!(groups[0].badge().unwrap_err().to_string(), "Rucksacks 1 to 2 have no item in common");
     }
 }
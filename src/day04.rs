@@ -1,6 +1,5 @@
-use std::ops::Range;
-
 use crate::harness::{Day, Part};
+use crate::interval::Interval;
 
 pub fn day04() -> Day<u32, u32> {
     Day::new(4, Box::new(Part1 {}), Box::new(Part2 {}))
@@ -14,21 +13,10 @@ impl Part<u32> for Part1 {
     }
 
     fn solve(&self, input: &Vec<String>) -> u32 {
-        input.into_iter()
-            .filter(|line| !line.is_empty())
-            .map(parse_line)
-            .map(|a| Self::score(a.get(0).unwrap(), a.get(1).unwrap()))
-            .sum()
-    }
-}
-
-impl Part1 {
-    fn score(r1: &Range<usize>, r2: &Range<usize>) -> u32 {
-        if range_contains_other(r1, r2) || range_contains_other(r2, r1) {
-            1
-        } else {
-            0
-        }
+        parse(input)
+            .iter()
+            .filter(|pair| pair.relation().is_containment())
+            .count() as u32
     }
 }
 
@@ -40,38 +28,59 @@ impl Part<u32> for Part2 {
     }
 
     fn solve(&self, input: &Vec<String>) -> u32 {
-        input.into_iter()
-            .filter(|line| !line.is_empty())
-            .map(parse_line)
-            .map(|a| Self::score(a.get(0).unwrap(), a.get(1).unwrap()))
-            .sum()
+        parse(input)
+            .iter()
+            .filter(|pair| pair.relation() != Relation::Disjoint)
+            .count() as u32
     }
 }
 
-impl Part2 {
-    fn score(r1: &Range<usize>, r2: &Range<usize>) -> u32 {
-        if ranges_overlap(r1, r2) {
-            1
-        } else {
-            0
-        }
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Relation {
+    Disjoint,
+    Overlapping,
+    FirstContainsSecond,
+    SecondContainsFirst,
+    Equal,
+}
+
+impl Relation {
+    fn is_containment(&self) -> bool {
+        matches!(self, Relation::FirstContainsSecond | Relation::SecondContainsFirst | Relation::Equal)
     }
 }
 
+#[derive(Debug, Copy, Clone)]
+struct Pair {
+    first: Interval,
+    second: Interval,
+}
 
-fn parse_line(line: &String) -> Vec<Range<usize>> {
-    line.split(",").map(parse_section).collect::<Vec<_>>()
+impl Pair {
+    fn relation(&self) -> Relation {
+        match (self.first.contains_interval(&self.second), self.second.contains_interval(&self.first)) {
+            (true, true) => Relation::Equal,
+            (true, false) => Relation::FirstContainsSecond,
+            (false, true) => Relation::SecondContainsFirst,
+            (false, false) if self.first.overlaps(&self.second) => Relation::Overlapping,
+            (false, false) => Relation::Disjoint,
+        }
+    }
 }
 
-fn parse_section(s: &str) -> Range<usize> {
-    let vec = s.split("-").map(|s| s.parse::<usize>().unwrap()).collect::<Vec<_>>();
-    *vec.get(0).unwrap()..*vec.get(1).unwrap() + 1
+fn parse(input: &[String]) -> Vec<Pair> {
+    input.iter()
+        .filter(|line| !line.is_empty())
+        .map(|line| parse_line(line))
+        .collect()
 }
 
-fn range_contains_other(a: &Range<usize>, b: &Range<usize>) -> bool {
-    a.start >= b.start && a.end <= b.end
+fn parse_line(line: &str) -> Pair {
+    let (first, second) = line.split_once(',').unwrap_or_else(|| panic!("Expected two assignments in {}", line));
+    Pair { first: parse_section(first), second: parse_section(second) }
 }
 
-fn ranges_overlap(a: &Range<usize>, b: &Range<usize>) -> bool {
-    a.contains(&b.start) || a.contains(&(b.end-1)) || b.contains(&a.start) || b.contains(&(a.end-1))
+fn parse_section(s: &str) -> Interval {
+    let (first, last) = s.split_once('-').unwrap_or_else(|| panic!("Expected a range of sections in {}", s));
+    Interval::inclusive(first.parse().unwrap(), last.parse().unwrap())
 }
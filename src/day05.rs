@@ -1,4 +1,6 @@
 use std::collections::vec_deque::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
 use crate::harness::{Day, Part};
 
@@ -14,16 +16,7 @@ impl Part<String> for Part1 {
     }
 
     fn solve(&self, input: &Vec<String>) -> String {
-        let (mut stacks, b) = parse(input);
-
-        for CraneMove { amount, from, to } in b {
-            for _ in 0..amount {
-                let from = stacks[from - 1].pop_back().unwrap();
-                stacks[to - 1].push_back(from);
-            }
-        }
-
-        stacks.into_iter().map(|s| *s.back().unwrap()).collect::<String>()
+        rearrange(&CrateMover9000, input)
     }
 }
 
@@ -35,47 +28,186 @@ impl Part<String> for Part2 {
     }
 
     fn solve(&self, input: &Vec<String>) -> String {
-        let (mut stacks, b) = parse(input);
+        rearrange(&CrateMover9001, input)
+    }
+}
 
-        for CraneMove { amount, from, to } in b {
-            let mut buffer = VecDeque::new();
+fn rearrange(crane: &dyn Crane, input: &[String]) -> String {
+    let (mut stacks, moves) = parse(input).unwrap_or_else(|e| panic!("{}", e));
 
-            for _ in 0..amount {
-                buffer.push_front(stacks[from - 1].pop_back().unwrap());
-            }
+    for (step, crane_move) in moves.iter().enumerate() {
+        crane.operate(&mut stacks, crane_move).unwrap_or_else(|e| panic!("Move {}: {}", step + 1, e));
+    }
+
+    stacks.tops()
+}
+
+trait Crane {
+    /// The order in which lifted crates, given bottom crate first, end up on the target stack.
+    fn arrange(&self, lifted: Vec<char>) -> Vec<char>;
 
-            for ch in buffer {
-                stacks[to - 1].push_back(ch);
+    /// Whether the crane can make the move at all, whatever the stacks hold.
+    fn check(&self, _crane_move: &CraneMove) -> Result<(), MoveError> {
+        Ok(())
+    }
+
+    fn operate(&self, stacks: &mut Stacks, crane_move: &CraneMove) -> Result<(), MoveError> {
+        self.check(crane_move)?;
+        let lifted = stacks.take(crane_move)?;
+        stacks.stack_mut(crane_move.to).extend(self.arrange(lifted));
+        Ok(())
+    }
+}
+
+/// Moves one crate at a time.
+struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn arrange(&self, mut lifted: Vec<char>) -> Vec<char> {
+        lifted.reverse();
+        lifted
+    }
+}
+
+/// Moves all crates at once.
+struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn arrange(&self, lifted: Vec<char>) -> Vec<char> {
+        lifted
+    }
+}
+
+/// Moves all crates at once like the CrateMover 9001, but can not lift more than `capacity` of them.
+struct LimitedCrateMover {
+    capacity: usize,
+}
+
+impl Crane for LimitedCrateMover {
+    fn arrange(&self, lifted: Vec<char>) -> Vec<char> {
+        lifted
+    }
+
+    fn check(&self, crane_move: &CraneMove) -> Result<(), MoveError> {
+        if crane_move.amount > self.capacity {
+            return Err(MoveError::OverCapacity { crane_move: *crane_move, capacity: self.capacity });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum MoveError {
+    NotEnoughCrates { crane_move: CraneMove, available: usize },
+    OverCapacity { crane_move: CraneMove, capacity: usize },
+}
+
+impl Display for MoveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveError::NotEnoughCrates { crane_move, available } =>
+                write!(f, "\"{}\" needs {} crates but stack {} only holds {}", crane_move, crane_move.amount, crane_move.from, available),
+            MoveError::OverCapacity { crane_move, capacity } =>
+                write!(f, "\"{}\" lifts {} crates but the crane only lifts {}", crane_move, crane_move.amount, capacity),
+        }
+    }
+}
+
+/// Applies moves one by one, yielding the stacks after every move and stopping at the first invalid one.
+struct Replay<'a> {
+    crane: &'a dyn Crane,
+    stacks: Stacks,
+    moves: std::slice::Iter<'a, CraneMove>,
+    failed: bool,
+}
+
+impl<'a> Replay<'a> {
+    pub fn new(crane: &'a dyn Crane, stacks: Stacks, moves: &'a [CraneMove]) -> Self {
+        Self { crane, stacks, moves: moves.iter(), failed: false }
+    }
+}
+
+impl Iterator for Replay<'_> {
+    type Item = Result<Stacks, MoveError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let crane_move = self.moves.next()?;
+        match self.crane.operate(&mut self.stacks, crane_move) {
+            Ok(()) => Some(Ok(self.stacks.clone())),
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
             }
         }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct ParseError {
+    line: usize,
+    column: Option<usize>,
+    message: String,
+}
+
+impl ParseError {
+    fn new(line: usize, column: Option<usize>, message: String) -> Self {
+        Self { line, column, message }
+    }
+}
 
-        stacks.into_iter().map(|s| *s.back().unwrap()).collect::<String>()
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.column {
+            Some(column) => write!(f, "Line {}, column {}: {}", self.line, column, self.message),
+            None => write!(f, "Line {}: {}", self.line, self.message),
+        }
     }
 }
 
-fn parse(input: &Vec<String>) -> (Vec<VecDeque<char>>, Vec<CraneMove>) {
-    let x = input.split(|line| line.is_empty()).collect::<Vec<_>>();
-    let stacks = x.get(0).unwrap();
-    let moves = x.get(1).unwrap();
+fn parse(input: &[String]) -> Result<(Stacks, Vec<CraneMove>), ParseError> {
+    let separator = input.iter().position(|line| line.is_empty()).ok_or_else(|| ParseError::new(1, None, "Missing blank line between the drawing and the moves".to_string()))?;
+
+    let stacks = Stacks::parse(&input[..separator])?;
+    let moves = input[separator + 1..]
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| {
+            let line_number = separator + i + 2;
+            let crane_move = line.parse::<CraneMove>().map_err(|e| ParseError::new(line_number, None, e))?;
+            for label in [crane_move.from, crane_move.to] {
+                if stacks.index_of(label).is_none() {
+                    return Err(ParseError::new(line_number, None, format!("There is no stack {}", label)));
+                }
+            }
+            Ok(crane_move)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
 
-    (parse_stacks(stacks), parse_moves(moves))
+    Ok((stacks, moves))
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 struct CraneMove {
     amount: usize,
     from: usize,
     to: usize,
 }
 
-impl From<&String> for CraneMove {
-    fn from(value: &String) -> Self {
-        let split = value.split(" ").collect::<Vec<_>>();
-        CraneMove::new(
-            split[1].parse::<usize>().unwrap(),
-            split[3].parse::<usize>().unwrap(),
-            split[5].parse::<usize>().unwrap(),
-        )
+impl FromStr for CraneMove {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = |token: &str| token.parse::<usize>().map_err(|e| format!("Invalid number {:?} in {:?}: {}", token, s, e));
+
+        match s.split_whitespace().collect::<Vec<_>>()[..] {
+            ["move", amount, "from", from, "to", to] => Ok(CraneMove::new(number(amount)?, number(from)?, number(to)?)),
+            _ => Err(format!("Expected \"move <amount> from <stack> to <stack>\" but got {:?}", s)),
+        }
     }
 }
 
@@ -85,39 +217,222 @@ impl CraneMove {
     }
 }
 
-fn parse_stacks(input: &[String]) -> Vec<VecDeque<char>> {
-    let x: &String = input.last().unwrap();
-    let number_of_stacks = (x.len() as f32 / 4.0).ceil() as usize;
+impl Display for CraneMove {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "move {} from {} to {}", self.amount, self.from, self.to)
+    }
+}
+
+/// The crate stacks, bottom crate first, each addressed by the label drawn underneath it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Stacks {
+    labels: Vec<usize>,
+    stacks: Vec<VecDeque<char>>,
+}
 
-    let mut result = vec![];
+impl Stacks {
+    /// Parses the drawing, the last line holds the labels and every crate sits above the label its `[X]` overlaps.
+    pub fn parse(drawing: &[String]) -> Result<Self, ParseError> {
+        let (label_line, crate_lines) = drawing.split_last().ok_or_else(|| ParseError::new(1, None, "Missing crate drawing".to_string()))?;
+        let label_line_number = drawing.len();
+
+        let mut labels = Vec::new();
+        let mut spans = Vec::new();
+        for (column, token) in tokens(label_line) {
+            let label = token.parse::<usize>().map_err(|_| ParseError::new(label_line_number, Some(column + 1), format!("Invalid stack label {:?}", token)))?;
+            if labels.contains(&label) {
+                return Err(ParseError::new(label_line_number, Some(column + 1), format!("Stack label {} appears twice", label)));
+            }
+            labels.push(label);
+            spans.push(column..column + token.chars().count());
+        }
 
-    for _ in 0..number_of_stacks {
-        result.push(VecDeque::new());
-    }
+        if labels.is_empty() {
+            return Err(ParseError::new(label_line_number, None, "Missing stack labels".to_string()));
+        }
 
-    let fold_into = |mut acc: Vec<VecDeque<char>>, item: &String| -> Vec<VecDeque<char>> {
-        let chars = item.chars().collect::<Vec<_>>();
+        let mut stacks = vec![VecDeque::new(); labels.len()];
+        let mut height = vec![0; labels.len()];
 
-        for i in 0..number_of_stacks {
-            let char_index = (i) * 4 + 1;
+        for (i, line) in crate_lines.iter().enumerate().rev() {
+            let line_number = i + 1;
+            let error = |column: usize, message: String| ParseError::new(line_number, Some(column + 1), message);
+            let level = crate_lines.len() - i - 1;
 
-            if let Some(ch) = chars.get(char_index) {
-                if ch.is_alphabetic() {
-                    acc[i].push_back(*ch);
+            for (column, token) in tokens(line) {
+                let chars = token.chars().collect::<Vec<_>>();
+                if chars.len() != 3 || chars[0] != '[' || chars[2] != ']' || !chars[1].is_alphabetic() {
+                    return Err(error(column, format!("Expected a crate like [A] but got {:?}", token)));
                 }
+
+                let cell = column..column + 3;
+                let stack = match spans.iter().position(|span| span.start < cell.end && cell.start < span.end) {
+                    Some(stack) => stack,
+                    None => return Err(error(column, format!("Crate {} is not above any stack label", token))),
+                };
+
+                if height[stack] != level {
+                    return Err(error(column, format!("Crate {} floats above an empty spot of stack {}", token, labels[stack])));
+                }
+
+                stacks[stack].push_back(chars[1]);
+                height[stack] += 1;
             }
         }
 
-        acc
-    };
+        Ok(Stacks { labels, stacks })
+    }
 
+    pub fn index_of(&self, label: usize) -> Option<usize> {
+        self.labels.iter().position(|&l| l == label)
+    }
 
-    input.iter().rev().skip(1).fold(result, fold_into)
+    pub fn stack_mut(&mut self, label: usize) -> &mut VecDeque<char> {
+        let index = self.index_of(label).unwrap_or_else(|| panic!("There is no stack {}", label));
+        &mut self.stacks[index]
+    }
+
+    /// Removes the crates a move picks up, bottom crate first, leaving the stacks untouched if there are not enough.
+    pub fn take(&mut self, crane_move: &CraneMove) -> Result<Vec<char>, MoveError> {
+        let stack = self.stack_mut(crane_move.from);
+        if stack.len() < crane_move.amount {
+            return Err(MoveError::NotEnoughCrates { crane_move: *crane_move, available: stack.len() });
+        }
+        Ok(stack.split_off(stack.len() - crane_move.amount).into())
+    }
+
+    /// The top crate of every stack, skipping empty ones.
+    pub fn tops(&self) -> String {
+        self.stacks.iter().filter_map(|s| s.back()).collect()
+    }
 }
 
+/// Draws the stacks the way the puzzle does, so `Stacks::parse` reads the drawing back unchanged.
+impl Display for Stacks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self.labels.iter().map(|l| l.to_string().len()).max().unwrap_or(0).max(3);
+        let height = self.stacks.iter().map(|s| s.len()).max().unwrap_or(0);
+
+        for level in (0..height).rev() {
+            let line = self.stacks.iter()
+                .map(|stack| match stack.get(level) {
+                    Some(c) => format!("{:^width$}", format!("[{}]", c), width = width),
+                    None => " ".repeat(width),
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(f, "{}", line)?;
+        }
 
-fn parse_moves(input: &[String]) -> Vec<CraneMove> {
-    input.iter().map(CraneMove::from).collect::<Vec<_>>()
+        let labels = self.labels.iter()
+            .map(|label| format!("{:^width$}", label, width = width))
+            .collect::<Vec<_>>()
+            .join(" ");
+        write!(f, "{}", labels)
+    }
 }
 
+/// Whitespace separated tokens of a line together with the column they start at.
+fn tokens(line: &str) -> Vec<(usize, &str)> {
+    let mut result = Vec::new();
+    let mut start = None;
+
+    for (column, (offset, c)) in line.char_indices().enumerate() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some((column, offset)),
+            (true, Some((start_column, start_offset))) => {
+                result.push((start_column, &line[start_offset..offset]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some((start_column, start_offset)) = start {
+        result.push((start_column, &line[start_offset..]));
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::{parse, CraneMove, CrateMover9000, CrateMover9001, LimitedCrateMover, MoveError, Replay, Stacks};
+
+    fn lines(drawing: &str) -> Vec<String> {
+        drawing.split('\n').map(String::from).collect()
+    }
+
+    #[test]
+    fn prints_the_example_drawing_back() {
+        let drawing = ["    [D]    ", "[N] [C]    ", "[Z] [M] [P]", " 1   2   3 "].join("\n");
+        let stacks = Stacks::parse(&lines(&drawing)).unwrap();
 
+        assert_eq!(stacks.to_string(), drawing);
+        assert_eq!(Stacks::parse(&lines(&stacks.to_string())), Ok(stacks));
+    }
+
+    #[test]
+    fn reads_drawings_with_multi_digit_labels_back() {
+        let stacks = Stacks {
+            labels: vec![9, 10, 11, 1234, 7],
+            stacks: vec![VecDeque::from(['A']), VecDeque::new(), VecDeque::from(['B', 'C', 'D']), VecDeque::from(['E', 'F']), VecDeque::from(['G'])],
+        };
+
+        let drawing = stacks.to_string();
+        assert_eq!(drawing, [
+            "          [D]           ",
+            "          [C]  [F]      ",
+            "[A]       [B]  [E]  [G] ",
+            " 9    10   11  1234  7  ",
+        ].join("\n"));
+        assert_eq!(Stacks::parse(&lines(&drawing)), Ok(stacks));
+    }
+
+    fn example() -> Vec<String> {
+        lines("    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 \n\nmove 1 from 2 to 1\nmove 3 from 1 to 3\nmove 2 from 2 to 1\nmove 1 from 1 to 2")
+    }
+
+    #[test]
+    fn replays_the_example_move_by_move() {
+        let (stacks, moves) = parse(&example()).unwrap();
+
+        let states = Replay::new(&CrateMover9000, stacks, &moves).map(|stacks| stacks.unwrap().to_string()).collect::<Vec<_>>();
+        assert_eq!(states, vec![
+            ["[D]        ", "[N] [C]    ", "[Z] [M] [P]", " 1   2   3 "].join("\n"),
+            ["        [Z]", "        [N]", "    [C] [D]", "    [M] [P]", " 1   2   3 "].join("\n"),
+            ["        [Z]", "        [N]", "[M]     [D]", "[C]     [P]", " 1   2   3 "].join("\n"),
+            ["        [Z]", "        [N]", "        [D]", "[C] [M] [P]", " 1   2   3 "].join("\n"),
+        ]);
+    }
+
+    #[test]
+    fn rejects_moves_from_stacks_without_enough_crates() {
+        let (stacks, _) = parse(&example()).unwrap();
+        let moves = [CraneMove::new(1, 3, 2), CraneMove::new(2, 3, 1), CraneMove::new(1, 1, 2)];
+
+        let mut replay = Replay::new(&CrateMover9001, stacks.clone(), &moves);
+        assert!(replay.next().unwrap().is_ok());
+        let error = replay.next().unwrap().unwrap_err();
+        assert_eq!(error, MoveError::NotEnoughCrates { crane_move: moves[1], available: 0 });
+        assert_eq!(error.to_string(), "\"move 2 from 3 to 1\" needs 2 crates but stack 3 only holds 0");
+        assert_eq!(replay.next(), None);
+
+        let mut short = stacks;
+        assert_eq!(short.take(&CraneMove::new(4, 2, 1)), Err(MoveError::NotEnoughCrates { crane_move: CraneMove::new(4, 2, 1), available: 3 }));
+        assert_eq!(short, parse(&example()).unwrap().0);
+    }
+
+    #[test]
+    fn limited_crane_rejects_lifting_too_many() {
+        let (stacks, _) = parse(&example()).unwrap();
+        let moves = [CraneMove::new(2, 2, 1), CraneMove::new(3, 1, 3)];
+
+        let states = Replay::new(&LimitedCrateMover { capacity: 2 }, stacks, &moves).collect::<Vec<_>>();
+        assert_eq!(states[0].as_ref().map(|stacks| stacks.tops()), Ok("DMP".to_string()));
+        assert_eq!(states[1], Err(MoveError::OverCapacity { crane_move: moves[1], capacity: 2 }));
+        assert_eq!(states[1].as_ref().unwrap_err().to_string(), "\"move 3 from 1 to 3\" lifts 3 crates but the crane only lifts 2");
+    }
+}
@@ -1,9 +1,10 @@
-use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::io::{BufReader, Bytes, Error, Read};
 
 use crate::harness::{Day, Part};
 
 pub fn day06() -> Day<u32, u32> {
-    Day::new(06, Box::new(Part1 {}), Box::new(Part2 {}))
+    Day::new(6, Box::new(Part1 {}), Box::new(Part2 {}))
 }
 
 pub struct Part1;
@@ -14,7 +15,7 @@ impl Part<u32> for Part1 {
     }
 
     fn solve(&self, input: &Vec<String>) -> u32 {
-        do_the_thing(input, 4)
+        first_marker(input, 4)
     }
 }
 
@@ -26,21 +27,137 @@ impl Part<u32> for Part2 {
     }
 
     fn solve(&self, input: &Vec<String>) -> u32 {
-        do_the_thing(input, 14)
+        first_marker(input, 14)
     }
 }
 
-fn do_the_thing(input: &Vec<String>, n: usize) -> u32 {
-    input[0]
-        .chars()
-        .collect::<Vec<_>>()
-        .windows(n)
-        .enumerate()
-        .find(|(_, arr)| are_all_elements_unique(arr))
-        .map(|(i, _)| (i + n) as u32)
+/// Position of the first marker in the first datastream that has one.
+fn first_marker(input: &[String], window: usize) -> u32 {
+    let datastreams = input.join("\n");
+
+    Markers::new(datastreams.as_bytes(), window)
+        .next()
+        .expect("No marker in any datastream")
         .unwrap()
+        .position as u32
+}
+
+/// Keeps the last `window` characters of a datastream along with how often each of them occurs.
+struct MarkerDetector {
+    window: usize,
+    recent: VecDeque<u8>,
+    counts: [usize; 256],
+    duplicates: usize,
+    position: usize,
+}
+
+impl MarkerDetector {
+    pub fn new(window: usize) -> Self {
+        assert!(window > 0, "A marker needs at least one character");
+        Self { window, recent: VecDeque::with_capacity(window + 1), counts: [0; 256], duplicates: 0, position: 0 }
+    }
+
+    /// Adds the next character, returning whether the last `window` characters are all different.
+    pub fn push(&mut self, c: u8) -> bool {
+        self.position += 1;
+
+        self.recent.push_back(c);
+        self.counts[c as usize] += 1;
+        if self.counts[c as usize] == 2 {
+            self.duplicates += 1;
+        }
+
+        if self.recent.len() > self.window {
+            let old = self.recent.pop_front().unwrap();
+            self.counts[old as usize] -= 1;
+            if self.counts[old as usize] == 1 {
+                self.duplicates -= 1;
+            }
+        }
+
+        self.recent.len() == self.window && self.duplicates == 0
+    }
+
+    /// Number of characters pushed so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Forgets every character, to start over with a new datastream.
+    pub fn reset(&mut self) {
+        self.recent.clear();
+        self.counts = [0; 256];
+        self.duplicates = 0;
+        self.position = 0;
+    }
+}
+
+/// A complete marker, at `position` characters into the datastream on `line`, both counted from 1.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Marker {
+    line: usize,
+    position: usize,
 }
 
-fn are_all_elements_unique(arr: &&[char]) -> bool {
-    HashSet::<_>::from_iter(arr.iter()).len() == arr.len()
+/// Every marker in a byte stream, read lazily. Each line is a separate datastream, so markers never span lines.
+struct Markers<R: Read> {
+    bytes: Bytes<BufReader<R>>,
+    detector: MarkerDetector,
+    line: usize,
+}
+
+impl<R: Read> Markers<R> {
+    pub fn new(reader: R, window: usize) -> Self {
+        Self { bytes: BufReader::new(reader).bytes(), detector: MarkerDetector::new(window), line: 1 }
+    }
+}
+
+impl<R: Read> Iterator for Markers<R> {
+    type Item = Result<Marker, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for byte in self.bytes.by_ref() {
+            match byte {
+                Ok(b'\n') => {
+                    self.line += 1;
+                    self.detector.reset();
+                }
+                Ok(b'\r') => {}
+                Ok(c) => {
+                    if self.detector.push(c) {
+                        return Some(Ok(Marker { line: self.line, position: self.detector.position() }));
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Marker, Markers};
+
+    fn first_per_line(datastreams: &str, window: usize) -> Vec<Marker> {
+        let mut markers = Markers::new(datastreams.as_bytes(), window).map(Result::unwrap).collect::<Vec<_>>();
+        markers.dedup_by_key(|marker| marker.line);
+        markers
+    }
+
+    #[test]
+    fn reads_every_line_as_its_own_datastream() {
+        let datastreams = "mjqjpqmgbljsphdztnvjfqwrcgsmlb\r\nbvwbjplbgvbhsrlpgdmjqwftvncz\nnppdvjthqldpwncqszvftbrmjlhg\nnznrnfrfntjfmvfwmzdfjlvtqnbhcprsg\nzcfzfwzzqfrljwzlrfnpqdbhtmscgvjw";
+
+        let positions = |window| first_per_line(datastreams, window).iter().map(|marker| (marker.line, marker.position)).collect::<Vec<_>>();
+        assert_eq!(positions(4), vec![(1, 7), (2, 5), (3, 6), (4, 10), (5, 11)]);
+        assert_eq!(positions(14), vec![(1, 19), (2, 23), (3, 23), (4, 29), (5, 26)]);
+    }
+
+    #[test]
+    fn never_finds_markers_across_line_breaks() {
+        let markers = Markers::new("aabc\ndeff".as_bytes(), 4).map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(markers, vec![]);
+    }
 }
@@ -1,14 +1,23 @@
-use std::borrow::Borrow;
-use std::cell::RefCell;
-use std::collections::VecDeque;
-use std::rc::Rc;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter, Write};
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+use rand::Rng;
+use rand::seq::SliceRandom;
 
 use crate::harness::{Day, Part};
+use crate::report::escape;
 
 pub fn day07() -> Day<u64, u64> {
     Day::new(7, Box::new(Part1 {}), Box::new(Part2 {}))
 }
 
+const SMALL_DIRECTORY: u64 = 100000;
+const DISK_SIZE: u64 = 70000000;
+const REQUIRED_SPACE: u64 = 30000000;
+
 pub struct Part1;
 
 impl Part<u64> for Part1 {
@@ -17,10 +26,11 @@ impl Part<u64> for Part1 {
     }
 
     fn solve(&self, input: &Vec<String>) -> u64 {
-        walk(parse_tree(input))
+        FileSystem::parse(input)
+            .unwrap_or_else(|e| panic!("{}", e))
+            .find(|d| d.size <= SMALL_DIRECTORY)
             .iter()
-            .map(|d| d.size())
-            .filter(|s| *s < 100000_u64)
+            .map(|d| d.size)
             .sum()
     }
 }
@@ -33,101 +43,682 @@ impl Part<u64> for Part2 {
     }
 
     fn solve(&self, input: &Vec<String>) -> u64 {
-        let root = parse_tree(input);
-        let vec = walk(root.clone());
+        FileSystem::parse(input)
+            .unwrap_or_else(|e| panic!("{}", e))
+            .deletion_candidates(DISK_SIZE, REQUIRED_SPACE)
+            .first()
+            .map_or(0, |d| d.size)
+    }
+}
+
+pub trait Sized {
+    fn size(&self) -> u64;
+}
+
+/// An absolute path, stored as its components below the root.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+struct Path(Vec<String>);
+
+impl Path {
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    /// Parses an absolute path like `/a/e`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.strip_prefix('/') {
+            Some(rest) => Ok(Path(rest.split('/').filter(|c| !c.is_empty()).map(|c| c.to_string()).collect())),
+            None => Err(format!("{:?} is not an absolute path", s)),
+        }
+    }
 
-        let current_usable = 70000000 - root.size();
-        let needed_space = 30000000 - current_usable;
+    pub fn components(&self) -> &[String] {
+        &self.0
+    }
 
-        let mut directories_with_sufficient_space = vec.into_iter().filter(|d| d.size() > needed_space).collect::<Vec<_>>();
+    pub fn join(&self, name: &str) -> Self {
+        let mut result = self.clone();
+        result.0.push(name.to_string());
+        result
+    }
 
-        directories_with_sufficient_space.sort_by_cached_key(|d| d.size());
+    /// The parent directory, the root being its own parent.
+    pub fn parent(&self) -> Self {
+        Path(self.0[..self.0.len().saturating_sub(1)].to_vec())
+    }
+}
 
-        directories_with_sufficient_space.first().unwrap().size()
+impl Display for Path {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "/");
+        }
+        self.0.iter().try_for_each(|c| write!(f, "/{}", c))
     }
 }
 
-pub trait Sized {
-    fn size(&self) -> u64;
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum CdTarget {
+    Root,
+    Parent,
+    Child(String),
 }
 
-#[derive(Debug)]
-struct Directory {
-    name: String,
-    directory_children: RefCell<Vec<Rc<Directory>>>,
-    file_children: RefCell<Vec<File>>,
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Entry {
+    Directory,
+    File(u64),
 }
 
-impl Directory {
-    pub fn new(name: String) -> Self {
-        Self { name, directory_children: RefCell::new(Vec::new()), file_children: RefCell::new(Vec::new()) }
+impl Display for Entry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Entry::Directory => write!(f, "a directory"),
+            Entry::File(size) => write!(f, "a file of size {}", size),
+        }
     }
+}
+
+/// One line of a terminal transcript, either a command or a line of `ls` output.
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Line {
+    Cd(CdTarget),
+    Ls,
+    Output(String, Entry),
+}
+
+impl FromStr for Line {
+    type Err = String;
 
-    pub fn push_file(&self, file: File) {
-        if self.file_children.borrow().iter().find(|f| f.name == file.name).is_some() {
-            return;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split(' ').collect::<Vec<_>>()[..] {
+            ["$", "cd", "/"] => Ok(Line::Cd(CdTarget::Root)),
+            ["$", "cd", ".."] => Ok(Line::Cd(CdTarget::Parent)),
+            ["$", "cd", name] if !name.is_empty() && !name.contains('/') => Ok(Line::Cd(CdTarget::Child(name.to_string()))),
+            ["$", "ls"] => Ok(Line::Ls),
+            ["dir", name] if !name.is_empty() => Ok(Line::Output(name.to_string(), Entry::Directory)),
+            [size, name] if !size.starts_with('$') && !name.is_empty() => {
+                let size = size.parse().map_err(|e| format!("Invalid size {:?}: {}", size, e))?;
+                Ok(Line::Output(name.to_string(), Entry::File(size)))
+            }
+            _ => Err(format!("Unrecognised line {:?}", s)),
         }
+    }
+}
 
-        self.file_children.borrow_mut().push(file);
+impl Display for Line {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Line::Cd(CdTarget::Root) => write!(f, "$ cd /"),
+            Line::Cd(CdTarget::Parent) => write!(f, "$ cd .."),
+            Line::Cd(CdTarget::Child(name)) => write!(f, "$ cd {}", name),
+            Line::Ls => write!(f, "$ ls"),
+            Line::Output(name, Entry::Directory) => write!(f, "dir {}", name),
+            Line::Output(name, Entry::File(size)) => write!(f, "{} {}", size, name),
+        }
     }
+}
 
-    pub fn push_directory(&self, directory: Directory) -> Rc<Directory> {
-        let result = Rc::new(directory);
-        self.directory_children.borrow_mut().push(result.clone());
-        result
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+struct Directory {
+    directories: BTreeMap<String, Directory>,
+    files: BTreeMap<String, File>,
+}
+
+impl Directory {
+    pub fn directory(&self, path: &Path) -> Option<&Directory> {
+        path.components().iter().try_fold(self, |directory, name| directory.directories.get(name))
+    }
+
+    /// The directory at `path`, creating it and any missing parents.
+    pub fn directory_mut(&mut self, path: &Path) -> &mut Directory {
+        path.components().iter().fold(self, |directory, name| directory.directories.entry(name.clone()).or_default())
+    }
+
+    /// Appends the size of every directory below and including this one, parents first, returning this one's size.
+    fn collect_usage(&self, path: Path, usage: &mut Vec<DirectoryUsage>) -> u64 {
+        let index = usage.len();
+        usage.push(DirectoryUsage { path: path.clone(), size: 0 });
+
+        let size = self.files.values().map(|f| f.size()).sum::<u64>() +
+            self.directories.iter().map(|(name, d)| d.collect_usage(path.join(name), usage)).sum::<u64>();
+
+        usage[index].size = size;
+        size
     }
 }
 
 impl Sized for Directory {
     fn size(&self) -> u64 {
-        self.directory_children.borrow().iter().map(|d| d.size()).sum::<u64>() +
-            self.file_children.borrow().iter().map(|d| d.size()).sum::<u64>()
+        self.directories.values().map(|d| d.size()).sum::<u64>() +
+            self.files.values().map(|f| f.size()).sum::<u64>()
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 struct File {
-    name: String,
     size: u64,
 }
 
-impl File {
-    pub fn new(name: String, size: u64) -> Self {
-        Self { name, size }
-    }
-}
-
 impl Sized for File {
     fn size(&self) -> u64 {
         self.size
     }
 }
 
-fn parse_tree(input: &Vec<String>) -> Rc<Directory> {
-    let mut stack: VecDeque<Rc<Directory>> = VecDeque::new();
-    stack.push_back(Rc::new(Directory::new("/".to_string())));
+/// Cumulative size of everything below a directory.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct DirectoryUsage {
+    path: Path,
+    size: u64,
+}
+
+/// The directory tree reconstructed from a terminal transcript, visiting a directory twice leaves a single copy.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+struct FileSystem {
+    root: Directory,
+}
+
+impl FileSystem {
+    pub fn parse(input: &[String]) -> Result<Self, String> {
+        let mut file_system = FileSystem::default();
+        let mut cwd = Path::root();
+
+        for (i, line) in input.iter().enumerate().filter(|(_, line)| !line.is_empty()) {
+            match line.parse::<Line>().map_err(|e| format!("Line {}: {}", i + 1, e))? {
+                Line::Cd(CdTarget::Root) => cwd = Path::root(),
+                Line::Cd(CdTarget::Parent) => cwd = cwd.parent(),
+                Line::Cd(CdTarget::Child(name)) => {
+                    cwd = cwd.join(&name);
+                    file_system.root.directory_mut(&cwd);
+                }
+                Line::Ls => {}
+                Line::Output(name, Entry::Directory) => file_system.mkdir(&cwd.join(&name)),
+                Line::Output(name, Entry::File(size)) => file_system.insert_file(&cwd.join(&name), size),
+            }
+        }
+
+        Ok(file_system)
+    }
+
+    /// Builds a file system from lines like `dir /a/e` and `584 /a/e/i`, the way `ls` would list them with absolute paths.
+    pub fn from_description(description: &[String]) -> Result<Self, String> {
+        let mut file_system = FileSystem::default();
+
+        for (i, line) in description.iter().enumerate().filter(|(_, line)| !line.is_empty()) {
+            let error = |message: String| format!("Line {}: {}", i + 1, message);
+            let (kind, path) = line.split_once(' ').ok_or_else(|| error(format!("Expected \"dir <path>\" or \"<size> <path>\" but got {:?}", line)))?;
+            let path = Path::parse(path).map_err(error)?;
+
+            match kind {
+                "dir" => file_system.mkdir(&path),
+                size => file_system.insert_file(&path, size.parse().map_err(|e| error(format!("Invalid size {:?}: {}", size, e)))?),
+            }
+        }
+
+        Ok(file_system)
+    }
+
+    /// Creates the directory at `path` along with any missing parents.
+    pub fn mkdir(&mut self, path: &Path) {
+        self.root.directory_mut(path);
+    }
+
+    /// Creates or overwrites the file at `path`, creating any missing parent directories.
+    pub fn insert_file(&mut self, path: &Path, size: u64) {
+        let name = path.components().last().unwrap_or_else(|| panic!("The root is not a file"));
+        self.root.directory_mut(&path.parent()).files.insert(name.clone(), File { size });
+    }
+
+    /// A `cd`/`ls` session that lists every directory exactly once, visiting and listing in an order picked by `rng`.
+    pub fn transcript(&self, rng: &mut impl Rng) -> Vec<String> {
+        let mut lines = vec![Line::Cd(CdTarget::Root)];
+        transcript_directory(&self.root, &Path::root(), rng, &mut lines);
+
+        while lines.len() > 1 && matches!(lines.last(), Some(Line::Cd(_))) {
+            lines.pop();
+        }
+
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    /// Total size of the directory at `path`, like `du -s`.
+    pub fn du(&self, path: &Path) -> Option<u64> {
+        self.root.directory(path).map(|d| d.size())
+    }
+
+    pub fn used(&self) -> u64 {
+        self.root.size()
+    }
+
+    /// Every directory with its cumulative size, parents before their children.
+    pub fn usage(&self) -> Vec<DirectoryUsage> {
+        let mut usage = Vec::new();
+        self.root.collect_usage(Path::root(), &mut usage);
+        usage
+    }
+
+    pub fn find(&self, predicate: impl Fn(&DirectoryUsage) -> bool) -> Vec<DirectoryUsage> {
+        self.usage().into_iter().filter(|d| predicate(d)).collect()
+    }
 
-    for cmd in input.iter().skip(1) {
-        match &cmd.split(" ").collect::<Vec<_>>()[..] {
-            ["$", "cd", "/"] => { stack.drain(1..); }
-            ["$", "cd", ".."] => { stack.pop_back(); }
-            ["$", "cd", directory] => { stack.push_back(stack.back().unwrap().push_directory(Directory::new(directory.to_string()))) }
-            ["$", _] | ["dir", _] => {}
-            [size, filename] => { stack.back().unwrap().push_file(File::new(filename.to_string(), size.parse().unwrap())) }
-            _ => {}
+    /// The `n` biggest directories, biggest first.
+    pub fn largest(&self, n: usize) -> Vec<DirectoryUsage> {
+        let mut usage = self.usage();
+        usage.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+        usage.truncate(n);
+        usage
+    }
+
+    /// Directories whose deletion alone frees `required` space on a disk of size `capacity`, smallest first.
+    pub fn deletion_candidates(&self, capacity: u64, required: u64) -> Vec<DirectoryUsage> {
+        let free = capacity.saturating_sub(self.used());
+        let missing = required.saturating_sub(free);
+        if missing == 0 {
+            return Vec::new();
         }
+
+        let mut candidates = self.find(|d| d.size >= missing);
+        candidates.sort_by(|a, b| a.size.cmp(&b.size).then_with(|| a.path.cmp(&b.path)));
+        candidates
     }
 
-    stack.front().unwrap().clone()
+    pub fn write_treemap(&self, path: &str, width: f64, height: f64) -> Result<(), Error> {
+        fs::write(path, self.treemap_svg(width, height))
+    }
+
+    /// Squarified treemap with one rectangle per file, nested inside a labelled rectangle per directory.
+    pub fn treemap_svg(&self, width: f64, height: f64) -> String {
+        let mut svg = String::new();
+        writeln!(svg, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-size=\"11\" font-family=\"monospace\">", width, height).unwrap();
+        writeln!(svg, "<rect width=\"{}\" height=\"{}\" fill=\"#0f0f23\"/>", width, height).unwrap();
+        treemap_directory(&mut svg, &Path::root(), &self.root, Rect { x: 0.0, y: 0.0, width, height }, 0);
+        svg.push_str("</svg>\n");
+        svg
+    }
 }
 
-fn walk(directory: Rc<Directory>) -> Vec<Rc<Directory>> {
-    let mut result = Vec::new();
-    result.push(directory.clone());
-    for child in directory.directory_children.borrow().iter() {
-        for walk_child in walk(child.clone()) {
-            result.push(walk_child.clone())
+fn transcript_directory(directory: &Directory, path: &Path, rng: &mut impl Rng, lines: &mut Vec<Line>) {
+    let mut listing = directory.directories.keys().map(|name| Line::Output(name.clone(), Entry::Directory))
+        .chain(directory.files.iter().map(|(name, file)| Line::Output(name.clone(), Entry::File(file.size()))))
+        .collect::<Vec<_>>();
+    listing.shuffle(rng);
+
+    lines.push(Line::Ls);
+    lines.extend(listing);
+
+    let mut children = directory.directories.iter().collect::<Vec<_>>();
+    children.shuffle(rng);
+
+    for (name, child) in children {
+        lines.push(Line::Cd(CdTarget::Child(name.clone())));
+        transcript_directory(child, &path.join(name), rng, lines);
+
+        // Now and then find the way back from the root instead of going up
+        if rng.gen_bool(0.2) {
+            lines.push(Line::Cd(CdTarget::Root));
+            lines.extend(path.components().iter().map(|c| Line::Cd(CdTarget::Child(c.clone()))));
+        } else {
+            lines.push(Line::Cd(CdTarget::Parent));
         }
     }
+}
+
+/// A transcript line that contradicts an earlier one or could not have come from the shell.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Inconsistency {
+    line: usize,
+    message: String,
+}
+
+impl Display for Inconsistency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+/// Replays a transcript and reports every line that contradicts what earlier lines established.
+fn validate(input: &[String]) -> Vec<Inconsistency> {
+    let mut issues = Vec::new();
+    let mut cwd = Path::root();
+    // Everything ever listed per directory, with the line it was first listed on
+    let mut listings: BTreeMap<Path, BTreeMap<String, (Entry, usize)>> = BTreeMap::new();
+    // Names shown by the `ls` currently producing output, and the line of that `ls`
+    let mut current: Option<(BTreeSet<String>, usize)> = None;
+
+    for (i, line) in input.iter().enumerate().filter(|(_, line)| !line.is_empty()) {
+        let number = i + 1;
+        let issue = |message: String| Inconsistency { line: number, message };
+
+        let line = match line.parse::<Line>() {
+            Ok(line) => line,
+            Err(e) => {
+                issues.push(issue(e));
+                continue;
+            }
+        };
+
+        if matches!(line, Line::Cd(_) | Line::Ls) {
+            if let Some((shown, ls_line)) = current.take() {
+                finish_listing(&listings[&cwd], &cwd, &shown, ls_line, &mut issues);
+            }
+        }
+
+        match line {
+            Line::Cd(CdTarget::Root) => cwd = Path::root(),
+            Line::Cd(CdTarget::Parent) if cwd == Path::root() => issues.push(issue("cd .. in the root directory".to_string())),
+            Line::Cd(CdTarget::Parent) => cwd = cwd.parent(),
+            Line::Cd(CdTarget::Child(name)) => {
+                let target = cwd.join(&name);
+                match listings.get(&cwd).map(|listing| listing.get(&name)) {
+                    None => issues.push(issue(format!("cd into {} before listing {}", target, cwd))),
+                    Some(None) => issues.push(issue(format!("cd into {} which no ls of {} showed", target, cwd))),
+                    Some(Some((Entry::File(_), listed))) => issues.push(issue(format!("cd into {} which line {} lists as a file", target, listed))),
+                    Some(Some((Entry::Directory, _))) => {}
+                }
+                cwd = target;
+            }
+            Line::Ls => {
+                listings.entry(cwd.clone()).or_default();
+                current = Some((BTreeSet::new(), number));
+            }
+            Line::Output(name, entry) => {
+                let Some((shown, _)) = current.as_mut() else {
+                    issues.push(issue(format!("{:?} is output without a preceding ls", input[i])));
+                    continue;
+                };
+
+                if !shown.insert(name.clone()) {
+                    issues.push(issue(format!("{} is listed twice by the same ls", cwd.join(&name))));
+                    continue;
+                }
+
+                let listing = listings.get_mut(&cwd).unwrap();
+                match listing.get(&name) {
+                    Some(&(earlier, listed)) if earlier != entry => issues.push(issue(format!("{} is listed as {} but line {} listed it as {}", cwd.join(&name), entry, listed, earlier))),
+                    Some(_) => {}
+                    None => {
+                        listing.insert(name, (entry, number));
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some((shown, ls_line)) = current {
+        finish_listing(&listings[&cwd], &cwd, &shown, ls_line, &mut issues);
+    }
+
+    issues.sort_by_key(|issue| issue.line);
+    issues
+}
+
+/// Reports entries an earlier `ls` of the same directory showed but this one did not.
+fn finish_listing(listing: &BTreeMap<String, (Entry, usize)>, cwd: &Path, shown: &BTreeSet<String>, ls_line: usize, issues: &mut Vec<Inconsistency>) {
+    for (name, (_, listed)) in listing.iter().filter(|(name, _)| !shown.contains(*name)) {
+        issues.push(Inconsistency { line: ls_line, message: format!("ls of {} does not show {} listed on line {}", cwd, name, listed) });
+    }
+}
+
+/// Prints the filesystem the terminal transcript in `input_path` explores, and optionally writes it as an SVG treemap.
+pub fn show_file_system(input_path: &str, treemap_path: Option<&str>, width: f64, height: f64) -> Result<(), Error> {
+    let input = fs::read_to_string(input_path)?.lines().map(String::from).collect::<Vec<_>>();
+    let file_system = FileSystem::parse(&input).map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}: {}", input_path, e)))?;
+
+    print!("{}", file_system);
+    if let Some(path) = treemap_path {
+        file_system.write_treemap(path, width, height)?;
+    }
+    Ok(())
+}
+
+/// Renders the tree like `tree` does, with the cumulative size of every directory.
+impl Display for FileSystem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "/ ({})", self.used())?;
+        write_tree(f, &self.root, "")
+    }
+}
+
+fn write_tree(f: &mut Formatter<'_>, directory: &Directory, prefix: &str) -> std::fmt::Result {
+    let entries = directory.directories.iter().map(|(name, d)| (format!("{}/", name), d.size(), Some(d)))
+        .chain(directory.files.iter().map(|(name, file)| (name.clone(), file.size(), None)))
+        .collect::<Vec<_>>();
+
+    for (i, (name, size, child)) in entries.iter().enumerate() {
+        let last = i + 1 == entries.len();
+        writeln!(f, "{}{} {} ({})", prefix, if last { "└──" } else { "├──" }, name, size)?;
+        if let Some(child) = child {
+            write_tree(f, child, &format!("{}{}", prefix, if last { "    " } else { "│   " }))?;
+        }
+    }
+
+    Ok(())
+}
+
+const TREEMAP_PADDING: f64 = 2.0;
+const TREEMAP_LABEL_HEIGHT: f64 = 13.0;
+const TREEMAP_COLORS: [&str; 4] = ["#9999cc", "#ffff66", "#00cc00", "#ff9966"];
+
+#[derive(Debug, Copy, Clone)]
+struct Rect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl Rect {
+    fn area(&self) -> f64 {
+        self.width * self.height
+    }
+
+    fn shrink(&self, top: f64, side: f64) -> Rect {
+        Rect { x: self.x + side, y: self.y + top, width: (self.width - 2.0 * side).max(0.0), height: (self.height - top - side).max(0.0) }
+    }
+}
+
+fn treemap_directory(svg: &mut String, path: &Path, directory: &Directory, rect: Rect, depth: usize) {
+    writeln!(
+        svg,
+        "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"none\" stroke=\"#333340\"><title>{} ({})</title></rect>",
+        rect.x, rect.y, rect.width, rect.height, escape(&path.to_string()), directory.size(),
+    ).unwrap();
+
+    let label_fits = rect.height > 2.0 * TREEMAP_LABEL_HEIGHT && rect.width > 40.0;
+    if label_fits {
+        writeln!(svg, "<text x=\"{:.1}\" y=\"{:.1}\" fill=\"#cccccc\">{}</text>", rect.x + TREEMAP_PADDING, rect.y + TREEMAP_LABEL_HEIGHT - 3.0, escape(&path.to_string())).unwrap();
+    }
+
+    let inner = rect.shrink(if label_fits { TREEMAP_LABEL_HEIGHT } else { TREEMAP_PADDING }, TREEMAP_PADDING);
+    let mut children = directory.directories.iter().map(|(name, d)| (name, d.size(), Some(d)))
+        .chain(directory.files.iter().map(|(name, file)| (name, file.size(), None)))
+        .filter(|(_, size, _)| *size > 0)
+        .collect::<Vec<_>>();
+    children.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+    let sizes = children.iter().map(|(_, size, _)| *size as f64).collect::<Vec<_>>();
+    for ((name, size, child), child_rect) in children.iter().zip(squarify(&sizes, inner)) {
+        let child_path = path.join(name);
+        match child {
+            Some(child) => treemap_directory(svg, &child_path, child, child_rect, depth + 1),
+            None => writeln!(
+                svg,
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\" stroke=\"#0f0f23\"><title>{} ({})</title></rect>",
+                child_rect.x, child_rect.y, child_rect.width, child_rect.height, TREEMAP_COLORS[depth % TREEMAP_COLORS.len()], escape(&child_path.to_string()), size,
+            ).unwrap(),
+        }
+    }
+}
+
+/// Splits `rect` into one rectangle per size, proportional in area, keeping aspect ratios close to 1.
+/// Expects the sizes in descending order, as described by Bruls, Huizing and van Wijk.
+fn squarify(sizes: &[f64], rect: Rect) -> Vec<Rect> {
+    let total = sizes.iter().sum::<f64>();
+    if total <= 0.0 || rect.area() <= 0.0 {
+        return vec![Rect { width: 0.0, height: 0.0, ..rect }; sizes.len()];
+    }
+
+    let areas = sizes.iter().map(|s| s / total * rect.area()).collect::<Vec<_>>();
+    let mut result = Vec::with_capacity(areas.len());
+    let mut remaining = rect;
+    let mut start = 0;
+
+    while start < areas.len() {
+        let side = remaining.width.min(remaining.height);
+        let mut end = start + 1;
+        while end < areas.len() && worst_ratio(&areas[start..=end], side) <= worst_ratio(&areas[start..end], side) {
+            end += 1;
+        }
+
+        let row = &areas[start..end];
+        let thickness = row.iter().sum::<f64>() / side;
+        let mut offset = 0.0;
+
+        for area in row {
+            let length = area / thickness;
+            if remaining.width >= remaining.height {
+                result.push(Rect { x: remaining.x, y: remaining.y + offset, width: thickness, height: length });
+            } else {
+                result.push(Rect { x: remaining.x + offset, y: remaining.y, width: length, height: thickness });
+            }
+            offset += length;
+        }
+
+        remaining = if remaining.width >= remaining.height {
+            Rect { x: remaining.x + thickness, width: (remaining.width - thickness).max(0.0), ..remaining }
+        } else {
+            Rect { y: remaining.y + thickness, height: (remaining.height - thickness).max(0.0), ..remaining }
+        };
+        start = end;
+    }
+
     result
 }
+
+/// The largest aspect ratio among a row of areas laid out along a side of length `side`.
+fn worst_ratio(row: &[f64], side: f64) -> f64 {
+    let sum = row.iter().sum::<f64>();
+    let max = row.iter().copied().fold(f64::MIN, f64::max);
+    let min = row.iter().copied().fold(f64::MAX, f64::min);
+    (side * side * max / (sum * sum)).max(sum * sum / (side * side * min))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::{validate, FileSystem, Path};
+
+    const RUNS: usize = 500;
+    const DIRECTORY_NAMES: [&str; 4] = ["a", "b", "cd", "efg"];
+    const FILE_NAMES: [&str; 5] = ["b.txt", "c.dat", "d.log", "i", "k"];
+
+    fn random_file_system(rng: &mut StdRng) -> FileSystem {
+        let mut file_system = FileSystem::default();
+
+        for _ in 0..rng.gen_range(0..20) {
+            let mut path = Path::root();
+            for _ in 0..rng.gen_range(0..4) {
+                path = path.join(DIRECTORY_NAMES[rng.gen_range(0..DIRECTORY_NAMES.len())]);
+            }
+
+            if rng.gen_bool(0.2) {
+                file_system.mkdir(&path);
+            } else {
+                file_system.insert_file(&path.join(FILE_NAMES[rng.gen_range(0..FILE_NAMES.len())]), rng.gen_range(1..1000000));
+            }
+        }
+
+        file_system
+    }
+
+    fn lines(s: &str) -> Vec<String> {
+        s.lines().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn generated_transcripts_round_trip() {
+        let mut rng = StdRng::seed_from_u64(7);
+
+        for _ in 0..RUNS {
+            let file_system = random_file_system(&mut rng);
+            let transcript = file_system.transcript(&mut rng);
+
+            assert_eq!(validate(&transcript), vec![], "{:#?}", transcript);
+            assert_eq!(FileSystem::parse(&transcript).unwrap(), file_system, "{:#?}", transcript);
+        }
+    }
+
+    #[test]
+    fn mangled_transcripts_do_not_panic() {
+        let mut rng = StdRng::seed_from_u64(77);
+
+        for _ in 0..RUNS {
+            let mut transcript = random_file_system(&mut rng).transcript(&mut rng);
+            for _ in 0..rng.gen_range(1..4) {
+                let i = rng.gen_range(0..transcript.len());
+                match rng.gen_range(0..3) {
+                    0 => { transcript.remove(i); }
+                    1 => transcript.insert(i, transcript[rng.gen_range(0..transcript.len())].clone()),
+                    _ => {
+                        let length = rng.gen_range(0..=transcript[i].len());
+                        transcript[i].truncate(length);
+                    }
+                }
+                if transcript.is_empty() {
+                    break;
+                }
+            }
+
+            let _ = FileSystem::parse(&transcript);
+            validate(&transcript);
+        }
+    }
+
+    #[test]
+    fn reports_inconsistencies_with_line_numbers() {
+        let transcript = lines("$ cd /\n$ ls\ndir a\n14848514 b.txt\n$ cd x\n$ cd /\n$ ls\ndir a\n200 b.txt\n$ cd ..\n12 c");
+        let issues = validate(&transcript).iter().map(|issue| issue.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(issues, vec![
+            "Line 5: cd into /x which no ls of / showed",
+            "Line 9: /b.txt is listed as a file of size 200 but line 4 listed it as a file of size 14848514",
+            "Line 10: cd .. in the root directory",
+            "Line 11: \"12 c\" is output without a preceding ls",
+        ]);
+    }
+
+    #[test]
+    fn builds_file_systems_from_descriptions() {
+        let description = FileSystem::from_description(&lines("dir /a/e\n584 /a/e/i\n14848514 /b.txt")).unwrap();
+        let transcript = FileSystem::parse(&lines("$ cd /\n$ ls\ndir a\n14848514 b.txt\n$ cd a\n$ ls\ndir e\n$ cd e\n$ ls\n584 i")).unwrap();
+
+        assert_eq!(description, transcript);
+        assert_eq!(description.du(&Path::parse("/a").unwrap()), Some(584));
+    }
+
+    #[test]
+    fn renders_the_example_as_a_tree() {
+        let transcript = lines("$ cd /\n$ ls\ndir a\n14848514 b.txt\n8504156 c.dat\ndir d\n$ cd a\n$ ls\ndir e\n29116 f\n2557 g\n62596 h.lst\n$ cd e\n$ ls\n584 i\n$ cd ..\n$ cd ..\n$ cd d\n$ ls\n4060174 j\n8033020 d.log\n5626152 d.ext\n7214296 k");
+        let file_system = FileSystem::parse(&transcript).unwrap();
+
+        assert_eq!(file_system.to_string(), "\
+/ (48381165)
+├── a/ (94853)
+│   ├── e/ (584)
+│   │   └── i (584)
+│   ├── f (29116)
+│   ├── g (2557)
+│   └── h.lst (62596)
+├── d/ (24933642)
+│   ├── d.ext (5626152)
+│   ├── d.log (8033020)
+│   ├── j (4060174)
+│   └── k (7214296)
+├── b.txt (14848514)
+└── c.dat (8504156)
+");
+    }
+}
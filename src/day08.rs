@@ -1,15 +1,20 @@
-use std::collections::HashSet;
-use std::fs::DirEntry;
-use std::iter::{Enumerate, FlatMap, Map};
-use std::slice::Iter;
+use std::fs;
+use std::io::Error;
+
+use colored::Colorize;
 
 use crate::harness::{Day, Part};
+use crate::image::{heat, Image, BLACK, WHITE};
 
 pub fn day08() -> Day<u32, u32> {
-    Day::new(8, Box::new(Part1 {}), Box::new(Part2 {}))
+    Day::new(8, Box::new(Part1 { views: Views::sweep }), Box::new(Part2 { views: Views::sweep }))
+        .with_part1("walk", Box::new(Part1 { views: Views::walk }))
+        .with_part2("walk", Box::new(Part2 { views: Views::walk }))
 }
 
-pub struct Part1;
+pub struct Part1 {
+    views: fn(&Grid) -> Views,
+}
 
 impl Part<u32> for Part1 {
     fn expect_test(&self) -> u32 {
@@ -17,11 +22,13 @@ impl Part<u32> for Part1 {
     }
 
     fn solve(&self, input: &Vec<String>) -> u32 {
-        count_visible(&parse(input))
+        (self.views)(&parse(input)).count_visible()
     }
 }
 
-pub struct Part2;
+pub struct Part2 {
+    views: fn(&Grid) -> Views,
+}
 
 impl Part<u32> for Part2 {
     fn expect_test(&self) -> u32 {
@@ -29,7 +36,7 @@ impl Part<u32> for Part2 {
     }
 
     fn solve(&self, input: &Vec<String>) -> u32 {
-        highest_scenic_score(&parse(input))
+        (self.views)(&parse(input)).highest_scenic_score()
     }
 }
 
@@ -42,8 +49,16 @@ impl Grid {
         Self { grid }
     }
 
+    pub fn width(&self) -> usize {
+        self.grid.first().map_or(0, |row| row.len())
+    }
+
+    pub fn height(&self) -> usize {
+        self.grid.len()
+    }
+
     fn get(&self, p: &Point) -> Option<u32> {
-        self.grid.get(p.y as usize).map(|arr| arr.get(p.x as usize).map(|i| *i)).flatten()
+        self.grid.get(p.y as usize).and_then(|arr| arr.get(p.x as usize)).copied()
     }
 }
 
@@ -69,51 +84,174 @@ const fn p(x: i32, y: i32) -> Point {
 
 const ORTHOGONAL_DIRECTIONS: [Point; 4] = [p(0, -1), p(-1, 0), p(0, 1), p(1, 0)];
 
-fn parse(input: &Vec<String>) -> Grid {
+fn parse(input: &[String]) -> Grid {
     let vec = input.iter().filter(|line| !line.is_empty()).map(|line| line.chars().map(|c| c.to_digit(10).unwrap()).collect::<Vec<_>>()).collect::<Vec<_>>();
     Grid::new(vec)
 }
 
-fn count_visible(grid: &Grid) -> u32 {
-    grid.grid.iter()
-        .enumerate()
-        .flat_map(|(y, row)|
-            row.iter()
-                .enumerate()
-                .map(move |(x, _)| p(x as i32, y as i32))
-        )
-        .map(|p|
-            if ORTHOGONAL_DIRECTIONS.iter().any(|d| is_visible(&grid, &p, d).0) { 1 } else { 0 }
-        )
-        .sum()
-}
-
-fn highest_scenic_score(grid: &Grid) -> u32 {
-    grid.grid.iter()
-        .enumerate()
-        .flat_map(|(y, arr)|
-            arr.iter()
-                .enumerate()
-                .map(move |(x, _)| p(x as i32, y as i32))
-        )
-        .map(|p|
-            ORTHOGONAL_DIRECTIONS.iter()
-                .map(|d| is_visible(&grid, &p, d))
-                .map(|r| r.1)
-                .product()
-        )
-        .max()
-        .unwrap()
-}
-
-fn is_visible(grid: &Grid, point: &Point, direction: &Point) -> (bool, u32) {
-    let mut location = point.clone();
+/// Prints the forest in `input_path` colored by scenic score, and optionally writes its heatmaps with `<prefix>` names.
+pub fn show_forest(input_path: &str, heatmap_prefix: Option<&str>, scale: usize) -> Result<(), Error> {
+    let input = fs::read_to_string(input_path)?.lines().map(String::from).collect::<Vec<_>>();
+    let grid = parse(&input);
+    let views = Views::sweep(&grid);
+
+    print!("{}", views.render(&grid));
+    if let Some(prefix) = heatmap_prefix {
+        views.write_heatmaps(prefix, scale)?;
+    }
+    Ok(())
+}
+
+/// Whether each tree can be seen from outside the forest, and its scenic score.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Views {
+    visible: Vec<Vec<bool>>,
+    scenic: Vec<Vec<u32>>,
+}
+
+impl Views {
+    /// Sweeps every row and column once in each direction, O(width * height) overall.
+    pub fn sweep(grid: &Grid) -> Self {
+        let (width, height) = (grid.width(), grid.height());
+        let mut views = Views { visible: vec![vec![false; width]; height], scenic: vec![vec![1; width]; height] };
+
+        for y in 0..height {
+            views.sweep_line(grid, &(0..width).map(|x| (x, y)).collect::<Vec<_>>());
+            views.sweep_line(grid, &(0..width).rev().map(|x| (x, y)).collect::<Vec<_>>());
+        }
+        for x in 0..width {
+            views.sweep_line(grid, &(0..height).map(|y| (x, y)).collect::<Vec<_>>());
+            views.sweep_line(grid, &(0..height).rev().map(|y| (x, y)).collect::<Vec<_>>());
+        }
+
+        views
+    }
+
+    /// Looks back along `line` from every tree, keeping a stack of the trees not yet hidden behind a taller or equal one.
+    fn sweep_line(&mut self, grid: &Grid, line: &[(usize, usize)]) {
+        let mut stack: Vec<(usize, u32)> = Vec::with_capacity(line.len());
+
+        for (i, &(x, y)) in line.iter().enumerate() {
+            let height = grid.grid[y][x];
+            while stack.last().is_some_and(|&(_, h)| h < height) {
+                stack.pop();
+            }
+
+            match stack.last() {
+                Some(&(blocker, _)) => self.scenic[y][x] *= (i - blocker) as u32,
+                None => {
+                    self.visible[y][x] = true;
+                    self.scenic[y][x] *= i as u32;
+                }
+            }
+
+            stack.push((i, height));
+        }
+    }
+
+    /// Walks outwards from every tree in all four directions, O(width * height * (width + height)) overall.
+    pub fn walk(grid: &Grid) -> Self {
+        let views_from = |x: usize, y: usize| ORTHOGONAL_DIRECTIONS.map(|d| look(grid, &p(x as i32, y as i32), &d));
+
+        Views {
+            visible: (0..grid.height()).map(|y| (0..grid.width()).map(|x| views_from(x, y).iter().any(|v| v.0)).collect()).collect(),
+            scenic: (0..grid.height()).map(|y| (0..grid.width()).map(|x| views_from(x, y).iter().map(|v| v.1).product()).collect()).collect(),
+        }
+    }
+
+    pub fn count_visible(&self) -> u32 {
+        self.visible.iter().flatten().filter(|v| **v).count() as u32
+    }
+
+    pub fn highest_scenic_score(&self) -> u32 {
+        self.scenic.iter().flatten().copied().max().unwrap()
+    }
+
+    /// The tree with the highest scenic score, the topmost and then leftmost one on ties.
+    pub fn best_location(&self) -> Option<(usize, usize)> {
+        let (x, y, _) = self.scenic.iter()
+            .enumerate()
+            .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, &score)| (x, y, score)))
+            .reduce(|best, next| if next.2 > best.2 { next } else { best })?;
+        Some((x, y))
+    }
+
+    /// White where a tree can be seen from outside the forest, black where it is hidden.
+    pub fn visibility_image(&self) -> Image {
+        Image::from_grid(&self.visible, |&visible| if visible { WHITE } else { BLACK })
+    }
+
+    /// Scenic scores on a heat scale, the best location being white.
+    pub fn scenic_image(&self) -> Image {
+        let best = self.highest_scenic_score().max(1) as f64;
+        Image::from_grid(&self.scenic, |&score| heat(score as f64 / best))
+    }
+
+    /// Writes `<prefix>_visibility.pgm` and `<prefix>_scenic.ppm`, every tree drawn as a `scale` pixels wide square.
+    pub fn write_heatmaps(&self, prefix: &str, scale: usize) -> Result<(), Error> {
+        self.visibility_image().scaled(scale).write_pgm(&format!("{}_visibility.pgm", prefix))?;
+        self.scenic_image().scaled(scale).write_ppm(&format!("{}_scenic.ppm", prefix))
+    }
+
+    /// Every tree's height on a background colored by its scenic score, with hidden trees dimmed
+    /// and the trees seen from the best location underlined.
+    pub fn render(&self, grid: &Grid) -> String {
+        let best = self.highest_scenic_score().max(1) as f64;
+        let best_location = self.best_location();
+
+        let mut in_sight = vec![vec![false; grid.width()]; grid.height()];
+        let mut distances = [0; 4];
+        if let Some((x, y)) = best_location {
+            for (direction, distance) in ORTHOGONAL_DIRECTIONS.iter().zip(distances.iter_mut()) {
+                *distance = look(grid, &p(x as i32, y as i32), direction).1;
+                let mut location = p(x as i32, y as i32);
+                for _ in 0..*distance {
+                    location = location.add(direction);
+                    in_sight[location.y as usize][location.x as usize] = true;
+                }
+            }
+        }
+
+        let mut result = String::new();
+        for (y, row) in grid.grid.iter().enumerate() {
+            for (x, height) in row.iter().enumerate() {
+                let [r, g, b] = heat(self.scenic[y][x] as f64 / best);
+                let mut cell = height.to_string().on_truecolor(r, g, b);
+                cell = if r as u32 + g as u32 + b as u32 > 384 { cell.black() } else { cell.white() };
+
+                if Some((x, y)) == best_location {
+                    cell = cell.bold().reversed();
+                } else if in_sight[y][x] {
+                    cell = cell.bold().underline();
+                } else if !self.visible[y][x] {
+                    cell = cell.dimmed();
+                }
+                result.push_str(&cell.to_string());
+            }
+            result.push('\n');
+        }
+
+        if let Some((x, y)) = best_location {
+            let [up, left, down, right] = distances;
+            result.push_str(&format!(
+                "Best location ({}, {}) scores {}, seeing {} up, {} left, {} down and {} right\n",
+                x, y, self.scenic[y][x], up, left, down, right,
+            ));
+        }
+
+        result
+    }
+}
+
+/// Whether the tree at `point` can be seen from outside in `direction`, and how many trees it sees that way.
+fn look(grid: &Grid, point: &Point, direction: &Point) -> (bool, u32) {
+    let mut location = *point;
     let original_height = grid.get(&location).unwrap();
 
     let mut count = 0;
 
     loop {
-        location = location.add(&direction);
+        location = location.add(direction);
         let current_height = grid.get(&location);
 
         match current_height {
@@ -125,3 +263,53 @@ fn is_visible(grid: &Grid, point: &Point, direction: &Point) -> (bool, u32) {
 
     (true, count)
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::{Grid, Views};
+    use crate::image::{BLACK, WHITE};
+
+    fn random_forest(rng: &mut StdRng, width: usize, height: usize, max_height: u32) -> Grid {
+        Grid::new((0..height).map(|_| (0..width).map(|_| rng.gen_range(0..=max_height)).collect()).collect())
+    }
+
+    #[test]
+    fn sweep_matches_walk_on_random_forests() {
+        let mut rng = StdRng::seed_from_u64(8);
+
+        for _ in 0..200 {
+            let (width, height, max_height) = (rng.gen_range(1..30), rng.gen_range(1..30), rng.gen_range(0..10));
+            let grid = random_forest(&mut rng, width, height, max_height);
+            assert_eq!(Views::sweep(&grid), Views::walk(&grid), "{:?}", grid.grid);
+        }
+    }
+
+    #[test]
+    fn sweep_matches_walk_on_large_forests() {
+        let mut rng = StdRng::seed_from_u64(88);
+
+        for (width, height, max_height) in [(150, 150, 9), (300, 40, 9), (1, 500, 9), (120, 120, 2)] {
+            let grid = random_forest(&mut rng, width, height, max_height);
+            assert_eq!(Views::sweep(&grid), Views::walk(&grid), "{}x{} forest", width, height);
+        }
+    }
+
+    #[test]
+    fn draws_the_example_heatmaps() {
+        let grid = Grid::new(["30373", "25512", "65332", "33549", "35390"].iter().map(|row| row.chars().map(|c| c.to_digit(10).unwrap()).collect()).collect());
+        let views = Views::sweep(&grid);
+
+        let visibility = views.visibility_image();
+        let pixels = (0..5).flat_map(|y| (0..5).map(move |x| (x, y))).map(|(x, y)| visibility.get(x, y)).collect::<Vec<_>>();
+        assert_eq!(pixels.iter().filter(|&&pixel| pixel == WHITE).count(), 21);
+        assert_eq!(visibility.get(3, 1), BLACK);
+
+        let scenic = views.scenic_image().scaled(3);
+        assert_eq!((scenic.width(), scenic.height()), (15, 15));
+        assert_eq!(scenic.get(2 * 3 + 1, 3 * 3 + 2), WHITE);
+        assert_eq!(scenic.get(0, 0), BLACK);
+    }
+}
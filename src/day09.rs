@@ -1,9 +1,12 @@
-use std::cmp::max;
 use std::collections::HashSet;
-use std::num::ParseIntError;
-use std::ops::Not;
+use std::fmt::{Display, Formatter};
+use std::io::{stdout, Error, ErrorKind, Write};
+use std::str::FromStr;
+use std::time::Duration;
+use std::{fs, iter, thread};
 
 use crate::harness::{Day, Part};
+use crate::image::Image;
 
 pub fn day09() -> Day<u32, u32> {
     Day::new(9, Box::new(Part1 {}), Box::new(Part2 {}))
@@ -33,11 +36,12 @@ impl Part<u32> for Part2 {
     }
 }
 
-fn solve(input: &Vec<String>, n: usize) -> u32 {
-    plot(n, &parse(input)).len() as u32
+fn solve(input: &[String], n: usize) -> u32 {
+    let moves = parse(input).unwrap_or_else(|e| panic!("{}", e));
+    visited(n, &moves).last().unwrap().len() as u32
 }
 
-#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
 struct Point {
     x: i32,
     y: i32,
@@ -58,78 +62,346 @@ impl Point {
     pub const fn add(&self, other: &Self) -> Self {
         Self::new(self.x + other.x, self.y + other.y)
     }
+
     pub const fn sub(&self, other: &Self) -> Self {
         Self::new(self.x - other.x, self.y - other.y)
     }
+
+    pub const fn signum(&self) -> Self {
+        Self::new(self.x.signum(), self.y.signum())
+    }
+
+    /// Distance when diagonal steps count as one, so touching points are at most 1 apart.
+    pub fn chebyshev(&self, other: &Self) -> i32 {
+        (self.x - other.x).abs().max((self.y - other.y).abs())
+    }
 }
 
 const fn p(x: i32, y: i32) -> Point {
     Point::new(x, y)
 }
 
-fn parse(input: &Vec<String>) -> Vec<Point> {
-    input.iter()
-        .filter(|line| line.is_empty().not())
-        .map(|line| parse_line(line))
-        .flat_map(|(direction, count)| vec![direction; count].into_iter())
-        .collect::<Vec<_>>()
+/// Moves the head `count` steps in `direction`, which may be diagonal.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Move {
+    direction: Point,
+    count: usize,
+}
+
+impl FromStr for Move {
+    type Err = String;
+
+    /// Reads moves like `R 4` or, for diagonals, `UL 2`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (letters, count) = s.split_once(' ').ok_or_else(|| format!("Expected \"<direction> <count>\" but got {:?}", s))?;
+
+        let mut direction = Point::ZERO;
+        for letter in letters.chars() {
+            let step = match letter {
+                'U' => Point::NORTH,
+                'R' => Point::EAST,
+                'D' => Point::SOUTH,
+                'L' => Point::WEST,
+                _ => return Err(format!("Unknown direction {:?} in {:?}", letter, s)),
+            };
+            if (step.x != 0 && direction.x != 0) || (step.y != 0 && direction.y != 0) {
+                return Err(format!("Direction {:?} moves along the same axis twice", letters));
+            }
+            direction = direction.add(&step);
+        }
+
+        if direction == Point::ZERO {
+            return Err(format!("Missing direction in {:?}", s));
+        }
+
+        let count = count.parse().map_err(|e| format!("Invalid step count {:?} in {:?}: {}", count, s, e))?;
+        Ok(Move { direction, count })
+    }
 }
 
-fn parse_line(line: &String) -> (Point, usize) {
-    let vec = line.split(" ").collect::<Vec<_>>();
-    let direction = match vec[0] {
-        "U" => Point::NORTH,
-        "R" => Point::EAST,
-        "D" => Point::SOUTH,
-        "L" => Point::WEST,
-        _ => panic!(),
-    };
+impl Display for Move {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let vertical = match self.direction.y { -1 => "U", 1 => "D", _ => "" };
+        let horizontal = match self.direction.x { -1 => "L", 1 => "R", _ => "" };
+        write!(f, "{}{} {}", vertical, horizontal, self.count)
+    }
+}
 
-    (direction, vec[1].parse().unwrap())
+fn parse(input: &[String]) -> Result<Vec<Move>, String> {
+    input.iter()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| line.parse().map_err(|e| format!("Line {}: {}", i + 1, e)))
+        .collect()
 }
 
+/// Knots from head to tail, each following the one before it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Rope {
+    knots: Vec<Point>,
+}
+
+impl Rope {
+    pub fn new(length: usize) -> Self {
+        assert!(length > 0, "A rope needs at least one knot");
+        Self { knots: vec![Point::ZERO; length] }
+    }
+
+    pub fn knots(&self) -> &[Point] {
+        &self.knots
+    }
 
-fn plot(n: usize, directions: &Vec<Point>) -> HashSet<Point> {
-    let start = p(0, 0);
-    let mut segments = vec![start; n];
+    pub fn tail(&self) -> Point {
+        *self.knots.last().unwrap()
+    }
 
-    let mut visited = vec![start].into_iter().collect::<HashSet<_>>();
-    for x in directions {
-        segments[0] = segments[0].add(&x);
+    /// Moves the head one step and lets the other knots catch up, returning how many knots moved.
+    pub fn step(&mut self, direction: Point) -> usize {
+        self.knots[0] = self.knots[0].add(&direction);
 
-        for i in 1..segments.len() {
-            let head = segments[i - 1];
-            let tail = segments[i];
+        for i in 1..self.knots.len() {
+            let (head, tail) = (self.knots[i - 1], self.knots[i]);
+            if head.chebyshev(&tail) <= 1 {
+                // Knots that stay put hold the rest of the rope in place
+                return i;
+            }
+            self.knots[i] = tail.add(&head.sub(&tail).signum());
+        }
 
-            let distance = head.sub(&tail);
+        self.knots.len()
+    }
+}
+
+/// The knot positions after every single step of the head, expanding moves one step at a time.
+struct Steps<'a> {
+    rope: Rope,
+    moves: std::slice::Iter<'a, Move>,
+    current: Option<Move>,
+}
+
+impl<'a> Steps<'a> {
+    pub fn new(length: usize, moves: &'a [Move]) -> Self {
+        Self { rope: Rope::new(length), moves: moves.iter(), current: None }
+    }
+}
 
-            let new_distance = dx(distance).add(&dy(&distance));
+impl Iterator for Steps<'_> {
+    type Item = Vec<Point>;
 
-            if new_distance != Point::ZERO {
-                segments[i] = head.add(&new_distance);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match &mut self.current {
+                Some(Move { direction, count }) if *count > 0 => {
+                    *count -= 1;
+                    let direction = *direction;
+                    self.rope.step(direction);
+                    return Some(self.rope.knots().to_vec());
+                }
+                _ => self.current = Some(*self.moves.next()?),
             }
         }
+    }
+}
 
-        segments = segments;
+/// Every position each knot has been at, from the head to the tail.
+fn visited(length: usize, moves: &[Move]) -> Vec<HashSet<Point>> {
+    let mut rope = Rope::new(length);
+    let mut visited = vec![HashSet::from([Point::ZERO]); length];
 
-        visited.insert(*segments.last().unwrap());
+    for head_move in moves {
+        for _ in 0..head_move.count {
+            let moved = rope.step(head_move.direction);
+            for (knot, visited) in rope.knots()[..moved].iter().zip(visited.iter_mut()) {
+                visited.insert(*knot);
+            }
+        }
     }
 
     visited
 }
 
-fn dy(distance: &Point) -> Point {
-    match distance.y {
-        2 => Point::NORTH,
-        -2 => Point::SOUTH,
-        _ => Point::ZERO,
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Cell {
+    Empty,
+    Trail,
+    Start,
+    Knot(usize),
+}
+
+/// Where [`animate`] sends its frames.
+pub enum AnimationOutput {
+    Terminal { fps: u32 },
+    TextFrames { directory: String },
+    PpmFrames { directory: String, scale: usize },
+}
+
+/// Animates the rope described by the moves in `input_path`.
+pub fn animate(input_path: &str, length: usize, output: &AnimationOutput) -> Result<(), Error> {
+    let input = fs::read_to_string(input_path)?.lines().map(String::from).collect::<Vec<_>>();
+    let moves = parse(&input).map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}: {}", input_path, e)))?;
+    let animation = Animation::new(length, &moves);
+
+    match output {
+        AnimationOutput::Terminal { fps } => animation.play(*fps),
+        AnimationOutput::TextFrames { directory } => animation.write_frames(directory, "txt", |canvas| animation.text(canvas).into_bytes()),
+        AnimationOutput::PpmFrames { directory, scale } => animation.write_frames(directory, "ppm", |canvas| animation.image(canvas).scaled(*scale).to_ppm()),
+    }
+}
+
+/// Draws the rope the way the puzzle does, on a canvas fixed to everything the rope reaches.
+struct Animation<'a> {
+    length: usize,
+    moves: &'a [Move],
+    min: Point,
+    max: Point,
+}
+
+impl<'a> Animation<'a> {
+    pub fn new(length: usize, moves: &'a [Move]) -> Self {
+        let (min, max) = Steps::new(length, moves)
+            .flatten()
+            .fold((Point::ZERO, Point::ZERO), |(min, max), knot| (p(min.x.min(knot.x), min.y.min(knot.y)), p(max.x.max(knot.x), max.y.max(knot.y))));
+
+        Self { length, moves, min, max }
+    }
+
+    pub fn step_count(&self) -> usize {
+        self.moves.iter().map(|m| m.count).sum()
+    }
+
+    /// The canvas before the first step and after every step, with the tail's trail so far.
+    pub fn frames(&self) -> impl Iterator<Item=Vec<Vec<Cell>>> + '_ {
+        let mut trail = HashSet::new();
+
+        iter::once(Rope::new(self.length).knots().to_vec())
+            .chain(Steps::new(self.length, self.moves))
+            .map(move |knots| {
+                trail.insert(*knots.last().unwrap());
+                self.canvas(&knots, &trail)
+            })
+    }
+
+    fn canvas(&self, knots: &[Point], trail: &HashSet<Point>) -> Vec<Vec<Cell>> {
+        let mut canvas = vec![vec![Cell::Empty; (self.max.x - self.min.x + 1) as usize]; (self.max.y - self.min.y + 1) as usize];
+        let mut draw = |point: &Point, cell: Cell| canvas[(point.y - self.min.y) as usize][(point.x - self.min.x) as usize] = cell;
+
+        trail.iter().for_each(|point| draw(point, Cell::Trail));
+        draw(&Point::ZERO, Cell::Start);
+        // Knots closer to the head cover the ones behind them
+        knots.iter().enumerate().rev().for_each(|(i, point)| draw(point, Cell::Knot(i)));
+
+        canvas
+    }
+
+    pub fn text(&self, canvas: &[Vec<Cell>]) -> String {
+        let mut text = String::new();
+        for row in canvas {
+            text.extend(row.iter().map(|cell| match cell {
+                Cell::Empty => '.',
+                Cell::Trail => '#',
+                Cell::Start => 's',
+                Cell::Knot(0) => 'H',
+                Cell::Knot(i) if *i == self.length - 1 && self.length == 2 => 'T',
+                // Knots after 9 continue with letters, which run out after z
+                Cell::Knot(i) => char::from_digit(*i as u32, 36).unwrap_or('*'),
+            }));
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn image(&self, canvas: &[Vec<Cell>]) -> Image {
+        Image::from_grid(canvas, |cell| match cell {
+            Cell::Empty => [15, 15, 35],
+            Cell::Trail => [102, 102, 102],
+            Cell::Start => [153, 153, 204],
+            Cell::Knot(0) => [255, 51, 51],
+            Cell::Knot(i) if *i == self.length - 1 => [0, 204, 0],
+            Cell::Knot(_) => [255, 255, 102],
+        })
+    }
+
+    /// Redraws the terminal for every frame, `fps` times a second.
+    pub fn play(&self, fps: u32) -> Result<(), Error> {
+        let delay = Duration::from_secs(1) / fps.max(1);
+        let mut stdout = stdout();
+
+        for (step, canvas) in self.frames().enumerate() {
+            write!(stdout, "\x1b[H\x1b[2JStep {} of {}\n\n{}", step, self.step_count(), self.text(&canvas))?;
+            stdout.flush()?;
+            thread::sleep(delay);
+        }
+
+        Ok(())
+    }
+
+    /// Writes every frame to `<directory>/frame_NNNNN.<extension>`.
+    pub fn write_frames(&self, directory: &str, extension: &str, encode: impl Fn(&[Vec<Cell>]) -> Vec<u8>) -> Result<(), Error> {
+        fs::create_dir_all(directory)?;
+        for (step, canvas) in self.frames().enumerate() {
+            fs::write(format!("{}/frame_{:0>5}.{}", directory, step, extension), encode(&canvas))?;
+        }
+        Ok(())
     }
 }
 
-fn dx(distance: Point) -> Point {
-    match distance.x {
-        2 => Point::WEST,
-        -2 => Point::EAST,
-        _ => Point::ZERO,
+#[cfg(test)]
+mod tests {
+    use super::{p, parse, visited, Animation, Move, Rope, Steps};
+
+    fn example() -> Vec<Move> {
+        parse(&["R 4", "U 4", "L 3", "D 1", "R 4", "D 1", "L 5", "R 2"].map(String::from)).unwrap()
+    }
+
+    #[test]
+    fn diagonal_moves_pull_the_knots_along_the_diagonal() {
+        let moves = ["UR 3".parse::<Move>().unwrap()];
+
+        assert_eq!(Steps::new(3, &moves).collect::<Vec<_>>(), vec![
+            vec![p(1, -1), p(0, 0), p(0, 0)],
+            vec![p(2, -2), p(1, -1), p(0, 0)],
+            vec![p(3, -3), p(2, -2), p(1, -1)],
+        ]);
+    }
+
+    #[test]
+    fn counts_the_positions_of_every_knot() {
+        let visited = visited(10, &example());
+
+        assert_eq!(visited[1].len(), 13);
+        assert_eq!(visited[9].len(), 1);
+    }
+
+    #[test]
+    fn last_step_is_the_final_rope() {
+        let moves = example();
+        let mut rope = Rope::new(10);
+        for head_move in &moves {
+            (0..head_move.count).for_each(|_| { rope.step(head_move.direction); });
+        }
+
+        assert_eq!(Steps::new(10, &moves).last(), Some(rope.knots().to_vec()));
+    }
+
+    #[test]
+    fn draws_frames_like_the_puzzle() {
+        let moves = example();
+        let animation = Animation::new(2, &moves);
+        let frames = animation.frames().map(|canvas| animation.text(&canvas)).collect::<Vec<_>>();
+
+        assert_eq!(frames.len(), animation.step_count() + 1);
+        assert_eq!(frames[0], "......\n......\n......\n......\nH.....\n");
+        assert_eq!(frames[1], "......\n......\n......\n......\nTH....\n");
+        assert_eq!(frames[5], "......\n......\n......\n....H.\ns##T..\n");
+        assert_eq!(frames.last().unwrap(), "..##..\n...##.\n.TH##.\n....#.\ns###..\n");
+    }
+
+    #[test]
+    fn names_knots_after_9_with_letters() {
+        let moves = ["R 12".parse::<Move>().unwrap()];
+        let animation = Animation::new(12, &moves);
+        let last = animation.frames().last().unwrap();
+
+        assert_eq!(animation.text(&last), "sba987654321H\n");
     }
 }
@@ -1,7 +1,9 @@
-use std::collections::HashMap;
-use std::fmt::Write;
+use std::fmt::{Display, Formatter};
+use std::ops::{Index, IndexMut};
+use std::str::FromStr;
 
 use crate::harness::{Day, Part};
+use crate::ocr::read_letters;
 
 pub fn day10() -> Day<i32, String> {
     Day::new(10, Box::new(Part1 {}), Box::new(Part2 {}))
@@ -15,7 +17,9 @@ impl Part<i32> for Part1 {
     }
 
     fn solve(&self, input: &Vec<String>) -> i32 {
-        solve(input).0
+        let mut probe = SignalProbe::new(20, 40);
+        run(input, &mut [&mut probe]);
+        probe.sum
     }
 }
 
@@ -30,70 +34,410 @@ impl Part<String> for Part2 {
 █████.....█████.....█████.....█████.....
 ██████......██████......██████......████
 ███████.......███████.......███████.....
+Unrecognised glyphs at columns 0..40 in "?"
         "#.trim().to_string()
     }
 
     fn solve(&self, input: &Vec<String>) -> String {
-        solve(input).1
+        let mut crt = Crt::new(40, 6);
+        run(input, &mut [&mut crt]);
+
+        read_screen(crt.to_string())
+    }
+}
+
+/// The letters on the screen, or the picture itself with where reading it failed when it does not show known letters.
+fn read_screen(drawing: String) -> String {
+    // The example draws a pattern rather than letters
+    match read_letters(&drawing) {
+        Ok(text) => text,
+        Err(e) => format!("{}\n{}", drawing, e),
+    }
+}
+
+fn run(input: &[String], peripherals: &mut [&mut dyn Peripheral]) {
+    let program = parse(input).unwrap_or_else(|e| panic!("{}", e));
+    match Cpu::new(program).run(peripherals) {
+        Stop::Halted => {}
+        stop => panic!("Unexpected stop: {}", stop),
+    }
+}
+
+fn parse(input: &[String]) -> Result<Vec<Instruction>, String> {
+    input.iter()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| line.parse().map_err(|e| format!("Line {}: {}", i + 1, e)))
+        .collect()
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+enum Register {
+    X,
+}
+
+impl Register {
+    pub const ALL: [Register; 1] = [Register::X];
+}
+
+impl Display for Register {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Register::X => write!(f, "X"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Registers([i32; Register::ALL.len()]);
+
+impl Default for Registers {
+    fn default() -> Self {
+        Registers([1])
+    }
+}
+
+impl Index<Register> for Registers {
+    type Output = i32;
+
+    fn index(&self, register: Register) -> &Self::Output {
+        &self.0[register as usize]
+    }
+}
+
+impl IndexMut<Register> for Registers {
+    fn index_mut(&mut self, register: Register) -> &mut Self::Output {
+        &mut self.0[register as usize]
+    }
+}
+
+impl Display for Registers {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let values = Register::ALL.iter().map(|&r| format!("{}={}", r, self[r])).collect::<Vec<_>>();
+        write!(f, "{}", values.join(" "))
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Instruction {
+    Noop,
+    Addx(i32),
+}
+
+impl Instruction {
+    /// How many cycles the instruction takes, its effect only becoming visible after the last one.
+    pub fn cycles(&self) -> u32 {
+        match self {
+            Instruction::Noop => 1,
+            Instruction::Addx(_) => 2,
+        }
+    }
+
+    pub fn execute(&self, registers: &mut Registers) {
+        match self {
+            Instruction::Noop => {}
+            Instruction::Addx(value) => registers[Register::X] += value,
+        }
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let argument = |value: &str| value.parse::<i32>().map_err(|e| format!("Invalid argument {:?} in {:?}: {}", value, s, e));
+
+        match s.split_whitespace().collect::<Vec<_>>()[..] {
+            ["noop"] => Ok(Instruction::Noop),
+            ["addx", value] => Ok(Instruction::Addx(argument(value)?)),
+            _ => Err(format!("Unknown instruction {:?}", s)),
+        }
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instruction::Noop => write!(f, "noop"),
+            Instruction::Addx(value) => write!(f, "addx {}", value),
+        }
     }
 }
 
-fn solve(input: &Vec<String>) -> (i32, String) {
-    let mut result_1 = 0;
-    let mut result_2 = "".to_string();
+/// Something attached to the CPU that sees the registers during every cycle.
+trait Peripheral {
+    fn tick(&mut self, cycle: u32, registers: &Registers);
+}
 
-    let mut x = 1;
-    let mut cycle = 1;
+/// Sums the signal strength, the cycle number times X, during every `interval`th cycle from `first` on.
+struct SignalProbe {
+    first: u32,
+    interval: u32,
+    sum: i32,
+}
 
-    append_result_2(x, cycle, &mut result_2);
+impl SignalProbe {
+    pub fn new(first: u32, interval: u32) -> Self {
+        Self { first, interval, sum: 0 }
+    }
+}
+
+impl Peripheral for SignalProbe {
+    fn tick(&mut self, cycle: u32, registers: &Registers) {
+        if cycle >= self.first && (cycle - self.first).is_multiple_of(self.interval) {
+            self.sum += cycle as i32 * registers[Register::X];
+        }
+    }
+}
+
+/// Draws one pixel per cycle, lit when the three pixel wide sprite centered on X covers it.
+struct Crt {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
 
-    for inst in parse(input).into_iter() {
-        if let Some(summand) = inst {
-            append_result_2(x, cycle, &mut result_2);
-            cycle += 1;
-            append_result_1(x, cycle, &mut result_1);
+impl Crt {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self { width, height, pixels: Vec::with_capacity(width * height) }
+    }
+}
 
-            x += summand;
+impl Peripheral for Crt {
+    fn tick(&mut self, _cycle: u32, registers: &Registers) {
+        if self.pixels.len() < self.width * self.height {
+            let column = (self.pixels.len() % self.width) as i32;
+            self.pixels.push((registers[Register::X] - column).abs() <= 1);
         }
+    }
+}
 
-        append_result_2(x, cycle, &mut result_2);
-        cycle += 1;
-        append_result_1(x, cycle, &mut result_1);
+impl Display for Crt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let rows = self.pixels.chunks(self.width)
+            .map(|row| row.iter().map(|&lit| if lit { '█' } else { '.' }).collect::<String>())
+            .collect::<Vec<_>>();
+        write!(f, "{}", rows.join("\n"))
     }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Breakpoint {
+    /// Stops before the cycle with this number starts.
+    Cycle(u32),
+    /// Stops before the instruction at this index starts.
+    Instruction(usize),
+}
 
-    (result_1, compose_result_2(result_2))
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Watchpoint {
+    /// Stops after any instruction that changes the register.
+    Changes(Register),
+    /// Stops after an instruction sets the register to the value.
+    Equals(Register, i32),
 }
 
-fn compose_result_2(result_2: String) -> String {
-    result_2
-        .chars()
-        .collect::<Vec<_>>()
-        .chunks(40)
-        .map(|w| w.iter().collect::<String>())
-        .take(6)
-        .collect::<Vec<_>>()
-        .join("\n")
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Stop {
+    Halted,
+    Breakpoint(Breakpoint),
+    Watchpoint { watchpoint: Watchpoint, old: i32, new: i32 },
 }
 
-fn append_result_1(x: i32, cycle: i32, result_1: &mut i32) {
-    if (cycle + 20) % 40 == 0 {
-        *result_1 = *result_1 + cycle * x;
+impl Display for Stop {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stop::Halted => write!(f, "halted"),
+            Stop::Breakpoint(Breakpoint::Cycle(cycle)) => write!(f, "breakpoint before cycle {}", cycle),
+            Stop::Breakpoint(Breakpoint::Instruction(index)) => write!(f, "breakpoint before instruction {}", index),
+            Stop::Watchpoint { watchpoint: Watchpoint::Changes(register) | Watchpoint::Equals(register, _), old, new } =>
+                write!(f, "watchpoint on {}, which went from {} to {}", register, old, new),
+        }
     }
 }
 
-fn append_result_2(x: i32, cycle: i32, result2: &mut String) {
-    let cycle_mod = cycle % 40;
-    if cycle_mod == x || cycle_mod - 1 == x || cycle_mod + 1 == x {
-        result2.push('█');
-    } else {
-        result2.push('.');
+/// The state during one cycle.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct TraceEntry {
+    cycle: u32,
+    pc: usize,
+    instruction: Instruction,
+    /// Which of the instruction's cycles this is, starting at 1.
+    progress: u32,
+    registers: Registers,
+}
+
+impl Display for TraceEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cycle {:>4}  {:>4}: {:<10} {}/{}  {}", self.cycle, self.pc, self.instruction.to_string(), self.progress, self.instruction.cycles(), self.registers)
     }
 }
 
-fn parse(input: &Vec<String>) -> Vec<Option<i32>> {
-    input.iter().map(|line| parse_line(line)).collect()
+struct Cpu {
+    program: Vec<Instruction>,
+    registers: Registers,
+    /// Index of the instruction currently executing, or the next one to start.
+    pc: usize,
+    /// Number of cycles completed.
+    cycle: u32,
+    /// Cycles of the current instruction already completed.
+    progress: u32,
+    breakpoints: Vec<Breakpoint>,
+    /// Cycles completed when a breakpoint last stopped the CPU, so resuming from there does not stop again.
+    stopped_at: Option<u32>,
+    watchpoints: Vec<Watchpoint>,
+    trace: Option<Vec<TraceEntry>>,
+}
+
+impl Cpu {
+    pub fn new(program: Vec<Instruction>) -> Self {
+        Self { program, registers: Registers::default(), pc: 0, cycle: 0, progress: 0, breakpoints: Vec::new(), stopped_at: None, watchpoints: Vec::new(), trace: None }
+    }
+
+    pub fn with_breakpoint(mut self, breakpoint: Breakpoint) -> Self {
+        self.breakpoints.push(breakpoint);
+        self
+    }
+
+    pub fn with_watchpoint(mut self, watchpoint: Watchpoint) -> Self {
+        self.watchpoints.push(watchpoint);
+        self
+    }
+
+    /// Records the state during every cycle from now on.
+    pub fn with_trace(mut self) -> Self {
+        self.trace = Some(Vec::new());
+        self
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    pub fn trace(&self) -> &[TraceEntry] {
+        self.trace.as_deref().unwrap_or_default()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.pc >= self.program.len()
+    }
+
+    /// Runs until the program ends or a breakpoint or watchpoint is hit, never stopping at the breakpoint it was resumed from.
+    pub fn run(&mut self, peripherals: &mut [&mut dyn Peripheral]) -> Stop {
+        while !self.is_halted() {
+            if self.stopped_at != Some(self.cycle) {
+                if let Some(&breakpoint) = self.breakpoints.iter().find(|b| self.is_at(b)) {
+                    self.stopped_at = Some(self.cycle);
+                    return Stop::Breakpoint(breakpoint);
+                }
+            }
+
+            if let Some(stop) = self.step(peripherals) {
+                return stop;
+            }
+        }
+
+        Stop::Halted
+    }
+
+    fn is_at(&self, breakpoint: &Breakpoint) -> bool {
+        match *breakpoint {
+            Breakpoint::Cycle(cycle) => self.cycle + 1 == cycle,
+            Breakpoint::Instruction(index) => self.pc == index && self.progress == 0,
+        }
+    }
+
+    /// Runs a single cycle, returning the watchpoint it triggered if any.
+    pub fn step(&mut self, peripherals: &mut [&mut dyn Peripheral]) -> Option<Stop> {
+        let instruction = *self.program.get(self.pc)?;
+        self.cycle += 1;
+        self.progress += 1;
+
+        for peripheral in peripherals.iter_mut() {
+            peripheral.tick(self.cycle, &self.registers);
+        }
+
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(TraceEntry { cycle: self.cycle, pc: self.pc, instruction, progress: self.progress, registers: self.registers });
+        }
+
+        if self.progress < instruction.cycles() {
+            return None;
+        }
+
+        let before = self.registers;
+        instruction.execute(&mut self.registers);
+        self.pc += 1;
+        self.progress = 0;
+
+        self.watchpoints.iter()
+            .find_map(|&watchpoint| {
+                let register = match watchpoint { Watchpoint::Changes(register) | Watchpoint::Equals(register, _) => register };
+                let (old, new) = (before[register], self.registers[register]);
+                let triggered = match watchpoint {
+                    Watchpoint::Changes(_) => old != new,
+                    Watchpoint::Equals(_, value) => old != new && new == value,
+                };
+                triggered.then_some(Stop::Watchpoint { watchpoint, old, new })
+            })
+    }
 }
 
-fn parse_line(line: &str) -> Option<i32> {
-    line.split(" ").collect::<Vec<_>>().last().unwrap().parse().ok()
+#[cfg(test)]
+mod tests {
+    use super::{parse, read_screen, Breakpoint, Cpu, Register, Stop, Watchpoint};
+    use crate::ocr::draw_letters;
+
+    fn example_cpu() -> Cpu {
+        Cpu::new(parse(&["noop", "addx 3", "addx -5", "noop"].map(String::from)).unwrap())
+    }
+
+    #[test]
+    fn stops_before_cycles() {
+        let mut cpu = example_cpu().with_breakpoint(Breakpoint::Cycle(1)).with_breakpoint(Breakpoint::Cycle(3)).with_trace();
+
+        assert_eq!(cpu.run(&mut []), Stop::Breakpoint(Breakpoint::Cycle(1)));
+        assert_eq!(cpu.trace().len(), 0);
+
+        assert_eq!(cpu.run(&mut []), Stop::Breakpoint(Breakpoint::Cycle(3)));
+        let last = cpu.trace().last().unwrap();
+        assert_eq!((last.cycle, last.pc, last.progress, last.registers[Register::X]), (2, 1, 1, 1));
+
+        assert_eq!(cpu.run(&mut []), Stop::Halted);
+        assert_eq!(cpu.trace().iter().map(|entry| entry.registers[Register::X]).collect::<Vec<_>>(), vec![1, 1, 1, 4, 4, -1]);
+    }
+
+    #[test]
+    fn stops_before_instructions() {
+        let mut cpu = example_cpu().with_breakpoint(Breakpoint::Instruction(0)).with_breakpoint(Breakpoint::Instruction(2));
+
+        assert_eq!(cpu.run(&mut []), Stop::Breakpoint(Breakpoint::Instruction(0)));
+        assert_eq!(cpu.run(&mut []), Stop::Breakpoint(Breakpoint::Instruction(2)));
+        assert_eq!((cpu.cycle, cpu.registers()[Register::X]), (3, 4));
+        assert_eq!(cpu.run(&mut []), Stop::Halted);
+        assert!(cpu.is_halted());
+    }
+
+    #[test]
+    fn stops_at_watched_values() {
+        let mut cpu = example_cpu().with_watchpoint(Watchpoint::Equals(Register::X, -1));
+
+        assert_eq!(cpu.run(&mut []), Stop::Watchpoint { watchpoint: Watchpoint::Equals(Register::X, -1), old: 4, new: -1 });
+        assert_eq!(cpu.cycle, 5);
+        assert_eq!(cpu.run(&mut []), Stop::Halted);
+
+        let mut cpu = example_cpu().with_watchpoint(Watchpoint::Changes(Register::X));
+        assert_eq!(cpu.run(&mut []), Stop::Watchpoint { watchpoint: Watchpoint::Changes(Register::X), old: 1, new: 4 });
+        assert_eq!(cpu.run(&mut []), Stop::Watchpoint { watchpoint: Watchpoint::Changes(Register::X), old: 4, new: -1 });
+        assert_eq!(cpu.run(&mut []), Stop::Halted);
+    }
+
+    #[test]
+    fn reads_letters_or_shows_where_reading_failed() {
+        let letters = draw_letters("HI").unwrap();
+        assert_eq!(read_screen(letters.clone()), "HI");
+
+        let garbled = letters.lines().map(|row| format!("{}.#", row)).collect::<Vec<_>>().join("\n");
+        assert_eq!(read_screen(garbled.clone()), format!("{}\nUnrecognised glyphs at columns 9..10 in \"HI?\"", garbled));
+    }
 }
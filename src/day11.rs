@@ -1,10 +1,15 @@
-use std::cell::RefCell;
-use std::fmt::{Debug, Formatter};
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind, Write};
+use std::str::FromStr;
 
 use crate::harness::{Day, Part};
 
 pub fn day11() -> Day<u128, u128> {
     Day::new(11, Box::new(Part1 {}), Box::new(Part2 {}))
+        .with_part2("cycles", Box::new(Part2Cycles {}))
 }
 
 pub struct Part1;
@@ -15,7 +20,9 @@ impl Part<u128> for Part1 {
     }
 
     fn solve(&self, input: &Vec<String>) -> u128 {
-        play(20, parse(input, true))
+        let mut simulation = Simulation::parse(input, true).unwrap_or_else(|e| panic!("{}", e));
+        simulation.play(20);
+        monkey_business(simulation.inspections())
     }
 }
 
@@ -27,139 +34,713 @@ impl Part<u128> for Part2 {
     }
 
     fn solve(&self, input: &Vec<String>) -> u128 {
-        play(10000, parse(input, false))
+        let mut simulation = Simulation::parse(input, false).unwrap_or_else(|e| panic!("{}", e));
+        simulation.play(10000);
+        monkey_business(simulation.inspections())
+    }
+}
+
+/// Part 2 by following every item until it repeats, rather than playing all the rounds.
+pub struct Part2Cycles;
+
+impl Part<u128> for Part2Cycles {
+    fn expect_test(&self) -> u128 {
+        2713310158
+    }
+
+    fn solve(&self, input: &Vec<String>) -> u128 {
+        let simulation = Simulation::parse(input, false).unwrap_or_else(|e| panic!("{}", e));
+        monkey_business(&simulation.extrapolate_inspections(10000))
     }
 }
 
 struct Monkey {
-    inspections: RefCell<u128>,
-    worry_levels: RefCell<Vec<u128>>,
     calculate_new_worry: Box<dyn Fn(u128) -> u128>,
     calculate_throw_to: Box<dyn Fn(u128) -> usize>,
 }
 
 impl Monkey {
-    pub fn new(worry_levels: Vec<u128>, calculate_new_worry: Box<dyn Fn(u128) -> u128>, calculate_throw_to: Box<dyn Fn(u128) -> usize>) -> Self {
-        Self {
-            inspections: RefCell::new(0),
-            worry_levels: RefCell::new(worry_levels),
-            calculate_new_worry,
-            calculate_throw_to,
+    pub fn new(calculate_new_worry: Box<dyn Fn(u128) -> u128>, calculate_throw_to: Box<dyn Fn(u128) -> usize>) -> Self {
+        Self { calculate_new_worry, calculate_throw_to }
+    }
+
+    /// The item's new worry level and the monkey it gets thrown to.
+    fn inspect(&self, worry: u128) -> (u128, usize) {
+        let worry = (self.calculate_new_worry)(worry);
+        (worry, (self.calculate_throw_to)(worry))
+    }
+}
+
+/// Builds the monkeys from their notes, dividing worry levels by 3 after every inspection in part 1
+/// and otherwise keeping them modulo the least common multiple of all divisors, which no test can tell apart.
+fn monkeys(notes: Vec<MonkeyNotes>, part_1: bool) -> Result<Vec<Monkey>, String> {
+    let modulus = modulus(&notes);
+
+    if !part_1 {
+        if modulus.is_none() {
+            return Err("The least common multiple of all divisors does not fit in 128 bits, so worry levels can not be kept modulo it".to_string());
+        }
+        if let Some(n) = notes.iter().find(|n| !n.operation.is_modular()) {
+            return Err(format!("Monkey {}: \"new = {}\" divides, so worry levels can not be kept modulo {}", n.id, n.operation, modulus.unwrap()));
         }
     }
 
-    fn inspect(&self) {
-        self.inspections.replace_with(|old| *old + self.worry_levels.borrow().len() as u128);
+    let modulus = modulus.unwrap_or(1);
+    Ok(notes.into_iter()
+        .map(|notes| {
+            let MonkeyNotes { id, operation, divisor, if_true, if_false, .. } = notes;
+
+            let calculate_new_worry: Box<dyn Fn(u128) -> u128> = if part_1 {
+                Box::new(move |old| operation.evaluate(old).unwrap_or_else(|| panic!("Monkey {}: \"new = {}\" has no worry level for old = {}", id, operation, old)) / 3)
+            } else {
+                Box::new(move |old| operation.evaluate_modulo(old, modulus))
+            };
 
-        let new_worry_levels = self.worry_levels.borrow().iter().map(|&it| (self.calculate_new_worry)(it)).collect::<Vec<u128>>();
-        self.worry_levels.borrow_mut().clear();
-        self.worry_levels.borrow_mut().extend(&new_worry_levels);
+            Monkey::new(calculate_new_worry, Box::new(move |worry| if worry % divisor == 0 { if_true } else { if_false }))
+        })
+        .collect())
+}
+
+/// What happened during one round.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct RoundStats {
+    round: u64,
+    /// Items each monkey inspected during the round.
+    inspections: Vec<u128>,
+    /// Worry levels of the items each monkey holds after the round.
+    items: Vec<Vec<u128>>,
+}
+
+struct Simulation {
+    monkeys: Vec<Monkey>,
+    items: Vec<Vec<u128>>,
+    inspections: Vec<u128>,
+    round: u64,
+}
+
+impl Simulation {
+    pub fn parse(input: &[String], part_1: bool) -> Result<Self, String> {
+        let notes = parse(input)?;
+        let items = notes.iter().map(|n| n.items.clone()).collect();
+        Ok(Self::new(monkeys(notes, part_1)?, items))
     }
 
-    fn receive(&self, item: u128) {
-        self.worry_levels.borrow_mut().push(item);
+    pub fn new(monkeys: Vec<Monkey>, items: Vec<Vec<u128>>) -> Self {
+        let inspections = vec![0; monkeys.len()];
+        Self { monkeys, items, inspections, round: 0 }
     }
 
-    fn throw_items(&self) -> Vec<(u128, usize)> {
-        let result = self.worry_levels.borrow().iter().map(|&worry| (worry, (self.calculate_throw_to)(worry))).collect::<Vec<_>>();
-        self.worry_levels.borrow_mut().clear();
-        result
+    /// Items inspected by each monkey over all rounds so far.
+    pub fn inspections(&self) -> &[u128] {
+        &self.inspections
     }
-}
 
-impl Debug for Monkey {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Monkey")
-            .field("inspections", &self.inspections.borrow())
-            .field("worry_levels", &self.worry_levels.borrow())
-            .finish()
+    pub fn items(&self) -> &[Vec<u128>] {
+        &self.items
     }
-}
 
-fn parse(input: &Vec<String>, part_1: bool) -> Vec<Monkey> {
-    input
-        .chunks(7)
-        .map(|it| parse_monkey(it, part_1))
-        .collect::<Vec<_>>()
-}
+    /// Plays a round and records what happened during it.
+    pub fn round(&mut self) -> RoundStats {
+        let inspections = self.play_round();
+        RoundStats { round: self.round, inspections, items: self.items.clone() }
+    }
 
-fn play(rounds: usize, monkeys: Vec<Monkey>) -> u128 {
-    for _ in 0..rounds {
-        for i in 0..monkeys.len() {
-            let monkey = &monkeys[i];
-            monkey.inspect();
-            for (item, to_monkey) in monkey.throw_items() {
-                monkeys[to_monkey].receive(item);
+    pub fn run(&mut self, rounds: usize) -> Vec<RoundStats> {
+        (0..rounds).map(|_| self.round()).collect()
+    }
+
+    /// Plays `rounds` rounds without recording them.
+    pub fn play(&mut self, rounds: u64) {
+        for _ in 0..rounds {
+            self.play_round();
+        }
+    }
+
+    /// Plays a round, returning the items each monkey inspected during it.
+    fn play_round(&mut self) -> Vec<u128> {
+        let mut inspections = vec![0; self.monkeys.len()];
+
+        for (i, monkey) in self.monkeys.iter().enumerate() {
+            inspections[i] += self.items[i].len() as u128;
+            for worry in std::mem::take(&mut self.items[i]) {
+                let (worry, to_monkey) = monkey.inspect(worry);
+                self.items[to_monkey].push(worry);
             }
         }
+
+        self.round += 1;
+        self.inspections.iter_mut().zip(&inspections).for_each(|(total, count)| *total += count);
+        inspections
     }
 
-    calculate_monkey_business(&monkeys)
+    /// Inspections per monkey after another `rounds` rounds, without playing them. Every item moves on its own,
+    /// so each is followed until its worry level and monkey at the start of a round repeat, and the cycle is extrapolated.
+    pub fn extrapolate_inspections(&self, rounds: u64) -> Vec<u128> {
+        let mut inspections = self.inspections.clone();
+
+        for (monkey, items) in self.items.iter().enumerate() {
+            for &worry in items {
+                let counts = self.extrapolate_item(worry, monkey, rounds);
+                inspections.iter_mut().zip(counts).for_each(|(total, count)| *total += count);
+            }
+        }
+
+        inspections
+    }
+
+    fn extrapolate_item(&self, worry: u128, monkey: usize, rounds: u64) -> Vec<u128> {
+        let mut seen = HashMap::new();
+        // Monkeys inspecting the item during every round played so far
+        let mut inspected_by = Vec::new();
+        let mut state = (worry, monkey);
+
+        let (cycle_start, cycle_length) = loop {
+            if inspected_by.len() as u64 == rounds {
+                break (inspected_by.len(), 0);
+            }
+            if let Some(&start) = seen.get(&state) {
+                break (start, inspected_by.len() - start);
+            }
+            seen.insert(state, inspected_by.len());
+
+            let (next, monkeys) = self.item_round(state);
+            inspected_by.push(monkeys);
+            state = next;
+        };
+
+        let mut counts = vec![0; self.monkeys.len()];
+        let mut count = |rounds: &[Vec<usize>], times: u128| rounds.iter().flatten().for_each(|&m| counts[m] += times);
+
+        count(&inspected_by[..cycle_start], 1);
+        if cycle_length > 0 {
+            let remaining = rounds - cycle_start as u64;
+            let cycle = &inspected_by[cycle_start..];
+            count(cycle, (remaining / cycle_length as u64) as u128);
+            count(&cycle[..(remaining % cycle_length as u64) as usize], 1);
+        }
+
+        counts
+    }
+
+    /// Where a single item ends up after a round, and which monkeys inspect it on the way. Monkeys take their
+    /// turns in order, so an item thrown to a monkey that has not had its turn yet is inspected again.
+    fn item_round(&self, (mut worry, mut monkey): (u128, usize)) -> ((u128, usize), Vec<usize>) {
+        let mut inspected_by = Vec::new();
+
+        loop {
+            inspected_by.push(monkey);
+            let (next_worry, to_monkey) = self.monkeys[monkey].inspect(worry);
+            worry = next_worry;
+
+            if to_monkey < monkey {
+                return ((worry, to_monkey), inspected_by);
+            }
+            monkey = to_monkey;
+        }
+    }
 }
 
-fn calculate_monkey_business(monkeys: &Vec<Monkey>) -> u128 {
-    let mut inspections = monkeys.iter().map(|monkey| *monkey.inspections.borrow()).collect::<Vec<_>>();
+fn monkey_business(inspections: &[u128]) -> u128 {
+    let mut inspections = inspections.to_vec();
     inspections.sort();
     inspections.reverse();
 
     inspections[0] * inspections[1]
 }
 
-fn parse_monkey(lines: &[String], part_1: bool) -> Monkey {
-    let worry_levels =
-        lines[1]
-            .split(":")
-            .nth(1)
-            .unwrap()
-            .trim()
-            .split(", ")
-            .map(|it| it.parse().unwrap())
-            .collect::<Vec<u128>>();
-
-    Monkey::new(
-        worry_levels,
-        parse_calculate_new_worry(lines, part_1),
-        parse_calculate_throw_to(lines),
-    )
-}
-
-fn parse_calculate_new_worry(lines: &[String], part_1: bool) -> Box<dyn Fn(u128) -> u128> {
-    let operation_raw =
-        lines[2]
-            .split("=")
-            .nth(1)
-            .unwrap()
-            .trim()
-            .split(" ")
-            .collect::<Vec<&str>>();
-
-    let operation = operation_raw[1].chars().next().unwrap();
-    let operand: Option<u128> = operation_raw[2].parse().ok();
-
-    Box::new(move |old| {
-        let other = operand.unwrap_or(old);
-        let r = match operation {
-            '*' => old * other,
-            '+' => old + other,
-            _ => panic!(),
+/// Plays `rounds` rounds, writing the items every monkey inspected during each one as a CSV row as soon as it is played.
+fn write_inspection_history(simulation: &mut Simulation, rounds: u64, out: impl Write) -> Result<(), Error> {
+    let mut out = BufWriter::new(out);
+
+    write!(out, "round")?;
+    for monkey in 0..simulation.monkeys.len() {
+        write!(out, ",monkey {}", monkey)?;
+    }
+    writeln!(out)?;
+
+    for _ in 0..rounds {
+        let inspections = simulation.play_round();
+        write!(out, "{}", simulation.round)?;
+        for count in inspections {
+            write!(out, ",{}", count)?;
+        }
+        writeln!(out)?;
+    }
+
+    out.flush()
+}
+
+/// Prints how many items every monkey described in `input_path` inspects over `rounds` rounds. With `csv_path`
+/// every round is played and recorded there, otherwise the rounds are extrapolated unless worry levels get divided by 3.
+pub fn report_inspections(input_path: &str, rounds: u64, relief: bool, csv_path: Option<&str>) -> Result<(), Error> {
+    let input = fs::read_to_string(input_path)?.lines().map(String::from).collect::<Vec<_>>();
+    let mut simulation = Simulation::parse(&input, relief).map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}: {}", input_path, e)))?;
+
+    let inspections = match csv_path {
+        Some(path) => {
+            write_inspection_history(&mut simulation, rounds, File::create(path)?)?;
+            simulation.inspections().to_vec()
+        }
+        // Dividing by 3 keeps worry levels from ever repeating, so there is no cycle to find
+        None if relief => {
+            simulation.play(rounds);
+            simulation.inspections().to_vec()
+        }
+        None => simulation.extrapolate_inspections(rounds),
+    };
+
+    for (monkey, count) in inspections.iter().enumerate() {
+        println!("Monkey {} inspected items {} times.", monkey, count);
+    }
+    println!("Monkey business after {} rounds: {}", rounds, monkey_business(&inspections));
+    Ok(())
+}
+
+/// The least common multiple of every monkey's divisor, or `None` if it overflows.
+fn modulus(notes: &[MonkeyNotes]) -> Option<u128> {
+    notes.iter().map(|n| n.divisor).try_fold(1, lcm)
+}
+
+fn gcd(a: u128, b: u128) -> u128 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+fn lcm(a: u128, b: u128) -> Option<u128> {
+    (a / gcd(a, b)).checked_mul(b)
+}
+
+/// `(a + b) % modulus` for `a` and `b` below `modulus`, without overflowing however large the modulus.
+fn add_mod(a: u128, b: u128, modulus: u128) -> u128 {
+    if a >= modulus - b { a - (modulus - b) } else { a + b }
+}
+
+/// `(a * b) % modulus` for `a` and `b` below `modulus`, falling back to doubling and adding when the product overflows.
+fn mul_mod(a: u128, b: u128, modulus: u128) -> u128 {
+    if let Some(product) = a.checked_mul(b) {
+        return product % modulus;
+    }
+
+    let (mut a, mut b, mut result) = (a, b, 0);
+    while b > 0 {
+        if b & 1 == 1 {
+            result = add_mod(result, a, modulus);
+        }
+        a = add_mod(a, a, modulus);
+        b >>= 1;
+    }
+    result
+}
+
+/// Everything the notes say about one monkey.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct MonkeyNotes {
+    id: usize,
+    items: Vec<u128>,
+    operation: Expr,
+    divisor: u128,
+    if_true: usize,
+    if_false: usize,
+}
+
+fn parse(input: &[String]) -> Result<Vec<MonkeyNotes>, String> {
+    let mut notes = Vec::new();
+    let mut start = 0;
+
+    for (i, line) in input.iter().chain([&String::new()]).enumerate() {
+        if line.trim().is_empty() {
+            if start < i {
+                notes.push(MonkeyNotes::parse(&input[start..i], start + 1)?);
+            }
+            start = i + 1;
+        }
+    }
+
+    for (index, n) in notes.iter().enumerate() {
+        if n.id != index {
+            return Err(format!("Expected the notes on monkey {} but got monkey {}", index, n.id));
+        }
+        for target in [n.if_true, n.if_false] {
+            if target >= notes.len() || target == n.id {
+                return Err(format!("Monkey {} can not throw to monkey {}", n.id, target));
+            }
+        }
+    }
+
+    Ok(notes)
+}
+
+impl MonkeyNotes {
+    /// Parses the six lines about one monkey, `first_line` being the line number of the first one.
+    fn parse(lines: &[String], first_line: usize) -> Result<Self, String> {
+        let line = |i: usize, prefix: &str| -> Result<&str, String> {
+            let text = lines.get(i).map(|line| line.trim()).ok_or_else(|| format!("Line {}: Missing \"{}\"", first_line + i, prefix))?;
+            text.strip_prefix(prefix).map(|rest| rest.trim()).ok_or_else(|| format!("Line {}: Unsupported note {:?}, expected \"{}...\"", first_line + i, text, prefix))
+        };
+        let number = |i: usize, text: &str| text.parse::<u128>().map_err(|e| format!("Line {}: Invalid number {:?}: {}", first_line + i, text, e));
+
+        if lines.len() > 6 {
+            return Err(format!("Line {}: Unsupported note {:?}", first_line + 6, lines[6].trim()));
+        }
+
+        let id = line(0, "Monkey")?.strip_suffix(':').ok_or_else(|| format!("Line {}: Expected \"Monkey <id>:\"", first_line))?;
+        let items = line(1, "Starting items:")?;
+        let operation = line(2, "Operation: new =")?;
+
+        Ok(MonkeyNotes {
+            id: number(0, id)? as usize,
+            items: items.split(',').map(|item| item.trim()).filter(|item| !item.is_empty()).map(|item| number(1, item)).collect::<Result<_, _>>()?,
+            operation: operation.parse().map_err(|e| format!("Line {}: {}", first_line + 2, e))?,
+            divisor: match number(3, line(3, "Test: divisible by")?)? {
+                0 => return Err(format!("Line {}: Worry levels can not be divisible by 0", first_line + 3)),
+                divisor => divisor,
+            },
+            if_true: number(4, line(4, "If true: throw to monkey")?)? as usize,
+            if_false: number(5, line(5, "If false: throw to monkey")?)? as usize,
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl Operator {
+    fn precedence(&self) -> u8 {
+        match self {
+            Operator::Add | Operator::Subtract => 1,
+            Operator::Multiply | Operator::Divide => 2,
+        }
+    }
+
+    fn symbol(&self) -> char {
+        match self {
+            Operator::Add => '+',
+            Operator::Subtract => '-',
+            Operator::Multiply => '*',
+            Operator::Divide => '/',
+        }
+    }
+}
+
+/// Arithmetic on the old worry level and literals.
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Expr {
+    Old,
+    Literal(u128),
+    Binary(Box<Expr>, Operator, Box<Expr>),
+}
+
+impl Expr {
+    /// The exact result, or `None` if it is negative, overflows or divides by zero.
+    pub fn evaluate(&self, old: u128) -> Option<u128> {
+        match self {
+            Expr::Old => Some(old),
+            Expr::Literal(value) => Some(*value),
+            Expr::Binary(left, operator, right) => {
+                let (left, right) = (left.evaluate(old)?, right.evaluate(old)?);
+                match operator {
+                    Operator::Add => left.checked_add(right),
+                    Operator::Subtract => left.checked_sub(right),
+                    Operator::Multiply => left.checked_mul(right),
+                    Operator::Divide => left.checked_div(right),
+                }
+            }
+        }
+    }
+
+    /// The result modulo `modulus`, which requires the expression to be [modular](Expr::is_modular).
+    pub fn evaluate_modulo(&self, old: u128, modulus: u128) -> u128 {
+        match self {
+            Expr::Old => old % modulus,
+            Expr::Literal(value) => value % modulus,
+            Expr::Binary(left, operator, right) => {
+                let (left, right) = (left.evaluate_modulo(old, modulus), right.evaluate_modulo(old, modulus));
+                match operator {
+                    Operator::Add => add_mod(left, right, modulus),
+                    Operator::Subtract => add_mod(left, (modulus - right) % modulus, modulus),
+                    Operator::Multiply => mul_mod(left, right, modulus),
+                    Operator::Divide => panic!("Division can not be evaluated modulo {}", modulus),
+                }
+            }
+        }
+    }
+
+    /// Whether the expression can be evaluated modulo any number, which division prevents.
+    pub fn is_modular(&self) -> bool {
+        match self {
+            Expr::Old | Expr::Literal(_) => true,
+            Expr::Binary(left, operator, right) => *operator != Operator::Divide && left.is_modular() && right.is_modular(),
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut position = 0;
+        let expr = parse_expr(&tokens, &mut position, 0).map_err(|e| format!("{} in {:?}", e, s))?;
+
+        match tokens.get(position) {
+            None => Ok(expr),
+            Some(token) => Err(format!("Unexpected {} in {:?}", token, s)),
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Old => write!(f, "old"),
+            Expr::Literal(value) => write!(f, "{}", value),
+            Expr::Binary(left, operator, right) => {
+                let needs_parentheses = |e: &Expr, right_side: bool| match e {
+                    Expr::Binary(_, inner, _) => inner.precedence() < operator.precedence() || (right_side && inner.precedence() == operator.precedence()),
+                    _ => false,
+                };
+                let write_operand = |f: &mut Formatter<'_>, e: &Expr, right_side: bool| {
+                    if needs_parentheses(e, right_side) { write!(f, "({})", e) } else { write!(f, "{}", e) }
+                };
+
+                write_operand(f, left, false)?;
+                write!(f, " {} ", operator.symbol())?;
+                write_operand(f, right, true)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Token {
+    Old,
+    Number(u128),
+    Operator(Operator),
+    Open,
+    Close,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Old => write!(f, "\"old\""),
+            Token::Number(value) => write!(f, "\"{}\"", value),
+            Token::Operator(operator) => write!(f, "\"{}\"", operator.symbol()),
+            Token::Open => write!(f, "\"(\""),
+            Token::Close => write!(f, "\")\""),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let token = match c {
+            ' ' => continue,
+            '+' => Token::Operator(Operator::Add),
+            '-' => Token::Operator(Operator::Subtract),
+            '*' => Token::Operator(Operator::Multiply),
+            '/' => Token::Operator(Operator::Divide),
+            '(' => Token::Open,
+            ')' => Token::Close,
+            c if c.is_ascii_alphanumeric() => {
+                let mut end = i + c.len_utf8();
+                while let Some(&(j, next)) = chars.peek() {
+                    if !next.is_ascii_alphanumeric() {
+                        break;
+                    }
+                    end = j + next.len_utf8();
+                    chars.next();
+                }
+
+                match &s[i..end] {
+                    "old" => Token::Old,
+                    word => Token::Number(word.parse().map_err(|_| format!("Unsupported operand {:?} in {:?}", word, s))?),
+                }
+            }
+            c => return Err(format!("Unsupported character {:?} in {:?}", c, s)),
         };
-        if part_1 {
-            r / 3
-        } else {
-            r % 223092870
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+/// Precedence climbing over `tokens`, only consuming operators binding at least as tightly as `min_precedence`.
+fn parse_expr(tokens: &[Token], position: &mut usize, min_precedence: u8) -> Result<Expr, String> {
+    let mut left = parse_operand(tokens, position)?;
+
+    while let Some(Token::Operator(operator)) = tokens.get(*position) {
+        if operator.precedence() < min_precedence {
+            break;
         }
-    })
+        *position += 1;
+        let right = parse_expr(tokens, position, operator.precedence() + 1)?;
+        left = Expr::Binary(Box::new(left), *operator, Box::new(right));
+    }
+
+    Ok(left)
 }
 
-fn parse_calculate_throw_to(lines: &[String]) -> Box<dyn Fn(u128) -> usize> {
-    let modulo: u128 = lines[3].split(" ").last().unwrap().parse().unwrap();
-    let divisible_index: usize = lines[4].split(" ").last().unwrap().parse().unwrap();
-    let not_divisible_index: usize = lines[5].split(" ").last().unwrap().parse().unwrap();
+fn parse_operand(tokens: &[Token], position: &mut usize) -> Result<Expr, String> {
+    let token = tokens.get(*position).ok_or("Missing operand")?;
+    *position += 1;
+
+    match token {
+        Token::Old => Ok(Expr::Old),
+        Token::Number(value) => Ok(Expr::Literal(*value)),
+        Token::Open => {
+            let inner = parse_expr(tokens, position, 0)?;
+            match tokens.get(*position) {
+                Some(Token::Close) => {
+                    *position += 1;
+                    Ok(inner)
+                }
+                _ => Err("Missing \")\"".to_string()),
+            }
+        }
+        token => Err(format!("Expected an operand but got {}", token)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{modulus, mul_mod, parse, write_inspection_history, Expr, Operator, Simulation};
+
+    const EXAMPLE: &str = "\
+Monkey 0:
+  Starting items: 79, 98
+  Operation: new = old * 19
+  Test: divisible by 23
+    If true: throw to monkey 2
+    If false: throw to monkey 3
+
+Monkey 1:
+  Starting items: 54, 65, 75, 74
+  Operation: new = old + 6
+  Test: divisible by 19
+    If true: throw to monkey 2
+    If false: throw to monkey 0
+
+Monkey 2:
+  Starting items: 79, 60, 97
+  Operation: new = old * old
+  Test: divisible by 13
+    If true: throw to monkey 1
+    If false: throw to monkey 3
+
+Monkey 3:
+  Starting items: 74
+  Operation: new = old + 3
+  Test: divisible by 17
+    If true: throw to monkey 0
+    If false: throw to monkey 1";
+
+    fn example() -> Vec<String> {
+        EXAMPLE.lines().map(String::from).collect()
+    }
+
+    fn expr(s: &str) -> Expr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn multiplies_before_adding_unless_parenthesized() {
+        let binary = |left: Expr, operator: Operator, right: Expr| Expr::Binary(Box::new(left), operator, Box::new(right));
+
+        assert_eq!(expr("old + 2 * old"), binary(Expr::Old, Operator::Add, binary(Expr::Literal(2), Operator::Multiply, Expr::Old)));
+        assert_eq!(expr("(old + 2) * old"), binary(binary(Expr::Old, Operator::Add, Expr::Literal(2)), Operator::Multiply, Expr::Old));
+
+        assert_eq!(expr("old + 2 * old").evaluate(5), Some(15));
+        assert_eq!(expr("(old + 2) * old").evaluate(5), Some(35));
+        assert_eq!(expr("(old + 2) * old").to_string(), "(old + 2) * old");
+        assert_eq!(expr("old - (2 - old)").to_string(), "old - (2 - old)");
+    }
+
+    #[test]
+    fn rejects_unknown_tokens() {
+        assert_eq!("old ^ 2".parse::<Expr>(), Err("Unsupported character '^' in \"old ^ 2\"".to_string()));
+        assert_eq!("old + x".parse::<Expr>(), Err("Unsupported operand \"x\" in \"old + x\"".to_string()));
+        assert_eq!("old +".parse::<Expr>(), Err("Missing operand in \"old +\"".to_string()));
+        assert_eq!("old old".parse::<Expr>(), Err("Unexpected \"old\" in \"old old\"".to_string()));
+    }
+
+    #[test]
+    fn rejects_division_without_relief() {
+        let input = example().into_iter().map(|line| line.replace("old + 6", "old / 2 + 6")).collect::<Vec<_>>();
+
+        assert!(Simulation::parse(&input, true).is_ok());
+        assert_eq!(Simulation::parse(&input, false).err(), Some("Monkey 1: \"new = old / 2 + 6\" divides, so worry levels can not be kept modulo 96577".to_string()));
+    }
+
+    #[test]
+    fn only_division_is_not_modular() {
+        assert!(expr("old * (old - 3) + 7").is_modular());
+        assert!(!expr("old / 2").is_modular());
+        assert!(!expr("old + (old * 3) / 2").is_modular());
+    }
+
+    #[test]
+    fn keeps_worry_levels_modulo_the_lcm_of_all_divisors() {
+        assert_eq!(modulus(&parse(&example()).unwrap()), Some(23 * 19 * 13 * 17));
 
-    Box::new(move |worry|
-        match worry % modulo {
-            0 => divisible_index,
-            _ => not_divisible_index,
+        let input = example().into_iter()
+            .map(|line| line.replace("by 23", "by 4").replace("by 19", "by 6").replace("by 13", "by 10").replace("by 17", "by 15"))
+            .collect::<Vec<_>>();
+        assert_eq!(modulus(&parse(&input).unwrap()), Some(60));
+    }
+
+    #[test]
+    fn multiplies_modulo_large_numbers_without_overflowing() {
+        let modulus = u128::MAX;
+
+        assert_eq!(mul_mod(modulus - 1, modulus - 2, modulus), 2);
+        assert_eq!(mul_mod(1 << 100, 1 << 100, (1 << 127) + 1), (1 << 127) + 1 - (1 << 73));
+        assert_eq!(expr("old * old - 1").evaluate_modulo(modulus - 1, modulus), 0);
+        assert_eq!(expr("old * old + 1").evaluate_modulo(1 << 64, modulus), 2);
+    }
+
+    #[test]
+    fn extrapolation_matches_playing_every_round() {
+        for rounds in [1, 20, 1000, 10000] {
+            let mut simulation = Simulation::parse(&example(), false).unwrap();
+            let extrapolated = simulation.extrapolate_inspections(rounds);
+            simulation.play(rounds);
+            assert_eq!(extrapolated, simulation.inspections(), "{} rounds", rounds);
+        }
+
+        let mut simulation = Simulation::parse(&example(), false).unwrap();
+        simulation.play(1000);
+        assert_eq!(simulation.inspections(), [5204, 4792, 199, 5192]);
+        let extrapolated = simulation.extrapolate_inspections(1234);
+        simulation.play(1234);
+        assert_eq!(extrapolated, simulation.inspections());
+    }
+
+    #[test]
+    fn writes_the_inspections_of_every_round() {
+        let mut simulation = Simulation::parse(&example(), false).unwrap();
+        let mut csv = Vec::new();
+        write_inspection_history(&mut simulation, 20, &mut csv).unwrap();
+
+        let csv = String::from_utf8(csv).unwrap();
+        let rows = csv.lines().collect::<Vec<_>>();
+        assert_eq!(rows.len(), 21);
+        assert_eq!(rows[0], "round,monkey 0,monkey 1,monkey 2,monkey 3");
+        assert_eq!(rows[1], "1,2,4,3,6");
+        assert_eq!(rows[20].split(',').next(), Some("20"));
+
+        let mut totals = vec![0; 4];
+        for row in &rows[1..] {
+            row.split(',').skip(1).zip(totals.iter_mut()).for_each(|(count, total)| *total += count.parse::<u128>().unwrap());
         }
-    )
+        assert_eq!(totals, [99, 97, 8, 103]);
+        assert_eq!(simulation.inspections(), totals);
+    }
 }
@@ -4,15 +4,20 @@ use std::cell::RefCell;
 use std::cmp::Reverse;
 use std::collections::{BTreeSet, HashSet, VecDeque};
 use std::fmt::{Display, Formatter};
+use std::fs;
 use std::hash::{Hash, Hasher};
+use std::io::{Error, ErrorKind};
 use std::rc::Rc;
 use std::thread::sleep;
 use std::time::Duration;
 
 use crate::harness::{Day, Part};
+use crate::image::Image;
 
 pub fn day12() -> Day<u32, u32> {
     Day::new(12, Box::new(Part1 {}), Box::new(Part2 {}))
+        .with_part1("astar", Box::new(Part1AStar {}))
+        .with_part2("astar", Box::new(Part2AStar {}))
 }
 
 pub struct Part1;
@@ -25,7 +30,7 @@ impl Part<u32> for Part1 {
     fn solve(&self, input: &Vec<String>) -> u32 {
         let map = Map::from(input);
 
-        Pathfinder::new(map).shortest_path()
+        DistanceField::to_end(&map).get(map.start).expect("The end can not be reached from the start")
     }
 }
 
@@ -39,16 +44,172 @@ impl Part<u32> for Part2 {
     fn solve(&self, input: &Vec<String>) -> u32 {
         let map = Map::from(input);
 
-        (0..map.raw.len()).map(|y| p(0, y as i32)).map(|p| {
-            let mut m = map.clone();
-            m.raw.iter().flat_map(|row| row.iter()).for_each(|node| { node.predecessor.replace(None); });
+        DistanceField::to_end(&map).closest(&map, |node| node.height() == char_to_height('a')).expect("The end can not be reached from any lowest cell").1
+    }
+}
+
+pub struct Part1AStar;
+
+impl Part<u32> for Part1AStar {
+    fn expect_test(&self) -> u32 {
+        31
+    }
+
+    fn solve(&self, input: &Vec<String>) -> u32 {
+        let map = Map::from(input);
+
+        Pathfinder::new(map).shortest_path().len() as u32 - 1
+    }
+}
+
+/// Runs A* from every lowest cell in turn.
+pub struct Part2AStar;
+
+impl Part<u32> for Part2AStar {
+    fn expect_test(&self) -> u32 {
+        29
+    }
+
+    fn solve(&self, input: &Vec<String>) -> u32 {
+        let map = Map::from(input);
+
+        let lowest = map.raw.iter().flatten().filter(|node| node.height() == char_to_height('a')).map(|node| node.position).collect::<Vec<_>>();
+
+        // Every search needs fresh nodes, and A* crosses impossible climbs at a high cost rather than not at all
+        lowest.into_iter().filter_map(|p| {
+            let mut m = Map::from(input);
             m.start = p;
-            let r = Pathfinder::new(m).shortest_path();
-            r
-        }).min().unwrap()
+            let route = Pathfinder::new(m).shortest_path();
+            route.windows(2).all(|step| map.get(step[1]).unwrap().height() <= map.get(step[0]).unwrap().height() + 1).then(|| route.len() as u32 - 1)
+        }).min().expect("The end can not be reached from any lowest cell")
     }
 }
 
+/// The fewest steps from every cell to the end, `None` where the end can not be reached.
+struct DistanceField {
+    distances: Vec<Vec<Option<u32>>>,
+}
+
+impl DistanceField {
+    /// Searches backwards from the end, stepping to the cells that could climb onto the current one.
+    pub fn to_end(map: &Map) -> Self {
+        let mut distances = map.raw.iter().map(|row| vec![None; row.len()]).collect::<Vec<_>>();
+        let mut queue = VecDeque::from([(map.end, 0)]);
+        distances[map.end.y as usize][map.end.x as usize] = Some(0);
+
+        while let Some((position, distance)) = queue.pop_front() {
+            let node = map.get(position).unwrap();
+
+            for d in ORTHOGONAL_DIRECTIONS {
+                let Some(neighbour) = map.get(position.add(d)) else { continue };
+                let seen = &mut distances[neighbour.position.y as usize][neighbour.position.x as usize];
+
+                if seen.is_none() && node.height() <= neighbour.height() + 1 {
+                    *seen = Some(distance + 1);
+                    queue.push_back((neighbour.position, distance + 1));
+                }
+            }
+        }
+
+        Self { distances }
+    }
+
+    pub fn get(&self, position: Point) -> Option<u32> {
+        *self.distances.get(position.y as usize)?.get(position.x as usize)?
+    }
+
+    /// The shortest route from `from` to the end, both included, by always stepping to a cell one closer.
+    pub fn route(&self, map: &Map, from: Point) -> Option<Vec<Point>> {
+        let mut route = vec![from];
+        let mut distance = self.get(from)?;
+
+        while distance > 0 {
+            let current = map.get(*route.last().unwrap()).unwrap();
+            let next = ORTHOGONAL_DIRECTIONS.iter()
+                .filter_map(|&d| map.get(current.position.add(d)))
+                .find(|next| self.get(next.position) == Some(distance - 1) && next.height() <= current.height() + 1)
+                .unwrap();
+
+            route.push(next.position);
+            distance -= 1;
+        }
+
+        Some(route)
+    }
+
+    /// The cell matching `filter` that is closest to the end, with its distance.
+    pub fn closest(&self, map: &Map, filter: impl Fn(&Node) -> bool) -> Option<(Point, u32)> {
+        map.raw.iter()
+            .flatten()
+            .filter(|node| filter(node))
+            .filter_map(|node| Some((node.position, self.get(node.position)?)))
+            .min_by_key(|&(_, distance)| distance)
+    }
+}
+
+
+/// Draws the route the way the puzzle does, every cell on it pointing to the next one.
+fn render_route(map: &Map, route: &[Point]) -> String {
+    let mut rows = map.raw.iter().map(|row| vec!['.'; row.len()]).collect::<Vec<_>>();
+
+    for step in route.windows(2) {
+        let arrow = match (step[1].x - step[0].x, step[1].y - step[0].y) {
+            (1, 0) => '>',
+            (-1, 0) => '<',
+            (0, 1) => 'v',
+            (0, -1) => '^',
+            _ => panic!("{} and {} are not next to each other", step[0], step[1]),
+        };
+        rows[step[0].y as usize][step[0].x as usize] = arrow;
+    }
+    if let Some(end) = route.last() {
+        rows[end.y as usize][end.x as usize] = 'E';
+    }
+
+    rows.iter().map(|row| row.iter().collect::<String>()).collect::<Vec<_>>().join("\n")
+}
+
+/// One pixel per cell, shading the terrain from dark valleys to bright peaks with the route drawn over it.
+fn route_image(map: &Map, route: &[Point]) -> Image {
+    let top = char_to_height('z') as f64;
+    let mut image = Image::from_grid(&map.raw, |node| {
+        let t = node.height() as f64 / top;
+        let blend = |low: f64, high: f64| (low + (high - low) * t).round() as u8;
+        [blend(30.0, 235.0), blend(70.0, 225.0), blend(30.0, 200.0)]
+    });
+
+    for point in route {
+        image.set(point.x as usize, point.y as usize, [255, 51, 51]);
+    }
+    if let (Some(start), Some(end)) = (route.first(), route.last()) {
+        image.set(start.x as usize, start.y as usize, [51, 153, 255]);
+        image.set(end.x as usize, end.y as usize, [255, 215, 0]);
+    }
+
+    image
+}
+
+/// Prints the shortest route through the heightmap in `input_path`, from its start or from whichever lowest cell
+/// is closest, and optionally writes it as a PPM with `scale` pixels per cell.
+pub fn draw_route(input_path: &str, from_lowest: bool, ppm_path: Option<&str>, scale: usize) -> Result<(), Error> {
+    let input = fs::read_to_string(input_path)?.lines().map(String::from).collect::<Vec<_>>();
+    let map = Map::from(&input);
+    let distances = DistanceField::to_end(&map);
+
+    let from = if from_lowest {
+        distances.closest(&map, |node| node.height() == char_to_height('a')).map(|(point, _)| point)
+    } else {
+        Some(map.start)
+    };
+    let route = from.and_then(|from| distances.route(&map, from))
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("{}: The end can not be reached", input_path)))?;
+
+    println!("{}\n\n{} steps from {}", render_route(&map, &route), route.len() - 1, route[0]);
+    if let Some(path) = ppm_path {
+        route_image(&map, &route).scaled(scale).write_ppm(path)?;
+    }
+    Ok(())
+}
 
 #[derive(Eq, PartialEq, Debug)]
 enum Weight {
@@ -197,7 +358,9 @@ impl Pathfinder {
     }
 
 
-    fn shortest_path(&mut self) -> u32 {
+    /// The route from the start to the end, both included.
+    fn shortest_path(&mut self) -> Vec<Point> {
+        self.map.start().set_g(0);
         self.open_list.push(self.map.start());
 
         while !self.open_list.is_empty() {
@@ -215,27 +378,7 @@ impl Pathfinder {
             self.expand_node(current_node.clone());
         }
 
-        let mut count = 0;
-
-        let mut current = Some(self.map.end().clone());
-
-        let mut arr = [[0; 173]; 41];
-
-        while let Some(node) = current {
-            count += 1;
-            let p = &node.position;
-            // println!("{:?}", p);
-
-            arr[p.y as usize][p.x as usize] = 1;
-
-            current = node.predecessor.clone().take();
-        }
-
-        // for x in arr {
-        //     println!("{}", x.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(""));
-        // };
-
-        count - 1
+        path(self.map.end()).iter().rev().map(|node| node.position).collect()
     }
 }
 
@@ -335,3 +478,39 @@ impl Display for Map {
 fn char_to_height(c: char) -> u32 {
     (c as u32) - 'a' as u32
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{char_to_height, p, render_route, DistanceField, Map, Part2AStar, Pathfinder, Point, ORTHOGONAL_DIRECTIONS};
+    use crate::harness::Part;
+
+    #[test]
+    fn finds_the_closest_lowest_cell_anywhere() {
+        let input = vec!["SzzzzzzzzzzzzzabcdefghijklmnopqrstuvwxyE".to_string(), "zzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzz".to_string()];
+        let map = Map::from(&input);
+        let distances = DistanceField::to_end(&map);
+
+        assert_eq!(distances.get(map.start), None);
+        assert_eq!(distances.closest(&map, |node| node.height() == char_to_height('a')), Some((p(14, 0), 25)));
+        assert_eq!(Part2AStar.solve(&input), 25);
+    }
+
+    #[test]
+    fn recovers_routes() {
+        let input = ["Sabqponm", "abcryxxl", "accszExk", "acctuvwj", "abdefghi"].map(String::from).to_vec();
+        let map = Map::from(&input);
+
+        let is_climb = |route: &[Point]| route.windows(2).all(|step| {
+            let (from, to) = (map.get(step[0]).unwrap(), map.get(step[1]).unwrap());
+            ORTHOGONAL_DIRECTIONS.contains(&p(step[1].x - step[0].x, step[1].y - step[0].y)) && to.height() <= from.height() + 1
+        });
+
+        let route = DistanceField::to_end(&map).route(&map, map.start).unwrap();
+        assert!(is_climb(&route));
+        assert_eq!(render_route(&map, &route), ["v..v<<<<", ">v.vv<<^", ".v.v>E^^", ".>v>>>^^", "..>>>>>^"].join("\n"));
+
+        let route = Pathfinder::new(Map::from(&input)).shortest_path();
+        assert!(is_climb(&route));
+        assert_eq!((route.first(), route.last(), route.len()), (Some(&map.start), Some(&map.end), 32));
+    }
+}
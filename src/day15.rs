@@ -3,6 +3,7 @@ use std::ops;
 use std::ops::{Add, Neg, Sub};
 
 use crate::harness::{Day, Part};
+use crate::interval::{Interval, IntervalSet};
 
 pub fn day15() -> Day<u32, u64> {
     Day::new(15, Box::new(Part1 {}), Box::new(Part2 {}))
@@ -21,18 +22,14 @@ impl Part<u32> for Part1 {
 
         let sensors = parse_sensors(input);
 
-        let manhattan_max = sensors.iter().map(|s| s.manhattan_radius).max().unwrap() as i32;
-        let min_x = sensors.iter().map(|s| s.sensor_location.x).min().unwrap() - manhattan_max;
-        let max_x = sensors.iter().map(|s| s.sensor_location.x).max().unwrap() + manhattan_max;
-
-        (min_x..=max_x)
-            .map(|x| p(x, line))
-            .filter(|&p|
-                sensors
-                    .iter()
-                    .any(|sensor| !sensor.collides(&p) && sensor.could_sense(&p))
-            )
-            .count() as u32
+        let covered = sensors.iter().map(|sensor| sensor.row_coverage(line)).collect::<IntervalSet>();
+        let beacons = sensors.iter()
+            .map(|sensor| sensor.beacon_location)
+            .filter(|beacon| beacon.y == line)
+            .map(|beacon| Interval::inclusive(beacon.x as i64, beacon.x as i64))
+            .collect::<IntervalSet>();
+
+        covered.difference(&beacons).len() as u32
     }
 }
 
@@ -158,4 +155,11 @@ impl Sensor {
     pub fn could_sense(&self, location: &Point) -> bool {
         self.sensor_location.manhattan_distance(location) <= self.manhattan_radius
     }
+
+    /// The x coordinates this sensor can see on row `y`.
+    pub fn row_coverage(&self, y: i32) -> Interval {
+        let reach = (self.manhattan_radius - (self.sensor_location.y - y).abs()) as i64;
+        let x = self.sensor_location.x as i64;
+        Interval::inclusive(x - reach, x + reach)
+    }
 }
@@ -12,13 +12,14 @@ use std::pin::Pin;
 use std::ptr::hash;
 use std::rc::Rc;
 use std::sync::mpsc::{channel, Sender};
-use std::task::{Context, Poll};
+use std::task::Poll;
 use std::thread;
 use std::time::{Duration, Instant, SystemTime};
 
 use rand::prelude::SliceRandom;
+use rand::Rng;
 
-use crate::harness::{Day, Part};
+use crate::harness::{Context, Day, Part};
 
 pub fn day16() -> Day<u32, u32> {
     Day::new(16, Box::new(Part1 {}), Box::new(Part2 {}))
@@ -46,9 +47,13 @@ impl Part<u32> for Part2 {
     }
 
     fn solve(&self, input: &Vec<String>) -> u32 {
+        self.solve_with_context(input, &Context::default())
+    }
+
+    fn solve_with_context(&self, input: &Vec<String>, context: &Context) -> u32 {
         let nodes = parse_nodes(input);
 
-        Search::new("AA".to_string(), nodes).search_part_2()
+        Search::new("AA".to_string(), nodes).search_part_2(&mut context.rng())
     }
 }
 
@@ -100,9 +105,9 @@ impl Search {
     }
 
 
-    fn search_part_2(&self) -> u32 {
+    fn search_part_2<R: Rng>(&self, rng: &mut R) -> u32 {
         // 24 cores go BRRR
-        let ranges = self.build_search_ranges(thread::available_parallelism().unwrap().get());
+        let ranges = self.build_search_ranges(thread::available_parallelism().unwrap().get(), rng);
         let n = ranges.len();
 
         let (tx, rx) = channel();
@@ -124,9 +129,9 @@ impl Search {
             .unwrap()
     }
 
-    fn build_search_ranges(&self, threads: usize) -> Vec<Vec<usize>> {
+    fn build_search_ranges<R: Rng>(&self, threads: usize, rng: &mut R) -> Vec<Vec<usize>> {
         let n = self.adjacency.len();
-        let bitsets = Self::generate_bitsets(n);
+        let bitsets = Self::generate_bitsets(n, rng);
 
         let chunk_size = max(1, (bitsets.len() as f64 / threads as f64).ceil() as usize);
 
@@ -137,9 +142,9 @@ impl Search {
             .collect::<Vec<_>>()
     }
 
-    fn generate_bitsets(n: usize) -> Vec<usize> {
+    fn generate_bitsets<R: Rng>(n: usize, rng: &mut R) -> Vec<usize> {
         let mut bitsets = (0_usize..(1 << n)).collect::<Vec<_>>();
-        bitsets.shuffle(&mut rand::thread_rng());
+        bitsets.shuffle(rng);
         bitsets
     }
 
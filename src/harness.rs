@@ -3,9 +3,14 @@
 use std::cmp::max;
 use std::fmt::{Debug, Display, Formatter};
 use std::fs;
+use std::rc::Rc;
 use std::time::{Duration, Instant};
 
 use colored::{ColoredString, Colorize};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::memory::peak_memory;
 
 pub trait AocResult: Display + Debug + PartialEq {}
 
@@ -14,6 +19,28 @@ impl<T: Display + Debug + PartialEq> AocResult for T {}
 pub trait Part<R: AocResult> {
     fn expect_test(&self) -> R;
     fn solve(&self, input: &Vec<String>) -> R;
+
+    /// Called by the harness instead of [`Part::solve`], override it for parts that need the [`Context`].
+    fn solve_with_context(&self, input: &Vec<String>, _context: &Context) -> R {
+        self.solve(input)
+    }
+}
+
+/// What the harness hands to every part it runs.
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    pub seed: u64,
+}
+
+impl Context {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// A fresh generator seeded with the run's seed, so every part sees the same sequence on every replay.
+    pub fn rng(&self) -> StdRng {
+        StdRng::seed_from_u64(self.seed)
+    }
 }
 
 pub struct EmptyPart {}
@@ -30,48 +57,149 @@ impl Part<String> for EmptyPart {
     }
 }
 
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum TestOutcome {
+    Passed,
+    Failed { expected: String, actual: String },
+    NotImplemented,
+    MissingInput,
+}
+
+#[derive(Debug, Clone)]
+pub struct PartReport {
+    pub id: u8,
+    pub test: TestOutcome,
+    pub test_duration: Duration,
+    pub answer: Option<String>,
+    pub duration: Duration,
+    pub memory: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct DayReport {
+    pub id: u8,
+    pub part1: PartReport,
+    pub part2: PartReport,
+}
+
+impl DayReport {
+    pub fn parts(&self) -> [&PartReport; 2] {
+        [&self.part1, &self.part2]
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.part1.duration + self.part2.duration
+    }
+}
+
 pub struct Day<R1: AocResult, R2: AocResult> {
     id: u8,
-    test_input: Vec<String>,
-    actual_input: Vec<String>,
+    test_input: Option<Vec<String>>,
+    actual_input: Option<Vec<String>>,
     part1: Box<dyn Part<R1>>,
     part2: Box<dyn Part<R2>>,
+    alternatives1: Vec<(String, Box<dyn Part<R1>>)>,
+    alternatives2: Vec<(String, Box<dyn Part<R2>>)>,
 }
 
 impl<R1: AocResult + 'static, R2: AocResult + 'static> Day<R1, R2> {
     pub fn new(id: u8, part1: Box<dyn Part<R1>>, part2: Box<dyn Part<R2>>) -> Self {
         Self {
             id,
-            test_input: read_input(format!("input/{:0>2}_test.txt", id).as_str()),
-            actual_input: read_input(format!("input/{:0>2}.txt", id).as_str()),
+            test_input: read_input(&test_input_path(id)),
+            actual_input: read_input(&actual_input_path(id)),
             part1,
             part2,
+            alternatives1: Vec::new(),
+            alternatives2: Vec::new(),
         }
     }
 
+    /// Registers another implementation of part 1, only run by [`Day::compare`].
+    pub fn with_part1(mut self, name: &str, part: Box<dyn Part<R1>>) -> Self {
+        self.alternatives1.push((name.to_string(), part));
+        self
+    }
+
+    /// Registers another implementation of part 2, only run by [`Day::compare`].
+    pub fn with_part2(mut self, name: &str, part: Box<dyn Part<R2>>) -> Self {
+        self.alternatives2.push((name.to_string(), part));
+        self
+    }
+
     fn timed<R: AocResult, F: Fn() -> R>(f: F) -> (R, Duration) {
         let start = Instant::now();
         let result = f();
         (result, start.elapsed())
     }
 
-    fn run_part_test<R: AocResult>(&self, id: u8, part: &Box<dyn Part<R>>) -> Duration {
+    fn evaluate_part_test<R: AocResult>(&self, part: &dyn Part<R>, context: &Context) -> (TestOutcome, Duration) {
         if part.expect_test().to_string() == NOT_IMPLEMENTED {
-            return Duration::ZERO;
+            return (TestOutcome::NotImplemented, Duration::ZERO);
         }
-        let (actual, duration) = Self::timed(|| { part.solve(&self.test_input) });
+        let input = match &self.test_input {
+            Some(input) => input,
+            None => return (TestOutcome::MissingInput, Duration::ZERO),
+        };
+        let (actual, duration) = Self::timed(|| { part.solve_with_context(input, context) });
         let expected = part.expect_test();
-        assert_eq!(actual, expected, "Part {} test failed after {:?}: Expected {} but got {}", id, duration, expected, actual);
-        println!("{}", format!("Part {} test        {} {:>10}", id, "successful".on_bright_green(), format!("{:?}", duration).purple()));
-        duration
+        let outcome = if actual == expected {
+            TestOutcome::Passed
+        } else {
+            TestOutcome::Failed { expected: expected.to_string(), actual: actual.to_string() }
+        };
+        (outcome, duration)
     }
 
-    fn run_part_actual<R: AocResult>(&self, id: u8, part: &Box<dyn Part<R>>) -> Duration {
+    fn evaluate_part_actual<R: AocResult>(&self, part: &dyn Part<R>, context: &Context) -> (Option<String>, Duration, usize) {
         if part.expect_test().to_string() == NOT_IMPLEMENTED {
-            return Duration::ZERO;
+            return (None, Duration::ZERO, 0);
+        }
+        match &self.actual_input {
+            Some(input) => {
+                let ((actual, duration), memory) = peak_memory(|| Self::timed(|| { part.solve_with_context(input, context) }));
+                (Some(actual.to_string()), duration, memory)
+            }
+            None => (None, Duration::ZERO, 0),
+        }
+    }
+
+    fn report_part<R: AocResult>(&self, id: u8, part: &dyn Part<R>, context: &Context) -> PartReport {
+        let (test, test_duration) = self.evaluate_part_test(part, context);
+        let (answer, duration, memory) = self.evaluate_part_actual(part, context);
+        PartReport { id, test, test_duration, answer, duration, memory }
+    }
+
+    fn run_part_test<R: AocResult>(&self, id: u8, part: &dyn Part<R>, context: &Context) -> (TestOutcome, Duration) {
+        let (outcome, duration) = self.evaluate_part_test(part, context);
+        match &outcome {
+            TestOutcome::Passed => {
+                println!("{}", format!("Part {} test        {} {:>10}", id, "successful".on_bright_green(), format!("{:?}", duration).purple()));
+            }
+            TestOutcome::Failed { .. } | TestOutcome::MissingInput => self.fail_test(id, &outcome, duration),
+            TestOutcome::NotImplemented => {}
+        }
+        (outcome, duration)
+    }
+
+    fn fail_test(&self, id: u8, outcome: &TestOutcome, duration: Duration) {
+        match outcome {
+            TestOutcome::Failed { expected, actual } => {
+                panic!("Part {} test failed after {:?}: Expected {} but got {}", id, duration, expected, actual);
+            }
+            TestOutcome::MissingInput => {
+                panic!("Part {} test input {} is missing", id, test_input_path(self.id));
+            }
+            TestOutcome::Passed | TestOutcome::NotImplemented => {}
         }
-        let (actual, duration) = Self::timed(|| { part.solve(&self.actual_input) });
-        let actual = actual.to_string();
+    }
+
+    fn run_part_actual<R: AocResult>(&self, id: u8, part: &dyn Part<R>, context: &Context) -> (Option<String>, Duration, usize) {
+        if part.expect_test().to_string() == NOT_IMPLEMENTED {
+            return (None, Duration::ZERO, 0);
+        }
+        let (answer, duration, memory) = self.evaluate_part_actual(part, context);
+        let actual = answer.clone().unwrap_or_else(|| panic!("Part {} input {} is missing", id, actual_input_path(self.id)));
         let (actual_colored, actual_multi_line_colored, max_pad) = if actual.lines().count() > 1 {
             (
                 " ".blue(),
@@ -89,51 +217,189 @@ impl<R1: AocResult + 'static, R2: AocResult + 'static> Day<R1, R2> {
         let pad_duration_by = max(0, max_pad - duration_string.chars().count() as i32);
         let duration_string = format!("{}{}", " ".repeat(pad_duration_by as usize), duration_string).purple();
         println!("{}{}", format!("Part {} output {:>15} {}", id, actual_colored, duration_string).on_blue(), actual_multi_line_colored);
-        duration
+        (answer, duration, memory)
+    }
+
+    fn run_part<R: AocResult>(&self, id: u8, part: &dyn Part<R>, context: &Context) -> PartReport {
+        let (test, test_duration) = self.run_part_test(id, part, context);
+        let (answer, duration, memory) = self.run_part_actual(id, part, context);
+        PartReport { id, test, test_duration, answer, duration, memory }
+    }
+
+    pub fn run_part1_test(&self, context: &Context) {
+        self.run_part_test(1, self.part1.as_ref(), context);
+    }
+
+    pub fn run_part2_test(&self, context: &Context) {
+        self.run_part_test(2, self.part2.as_ref(), context);
+    }
+
+    pub fn run_test(&self, context: &Context) {
+        self.run_part_test(1, self.part1.as_ref(), context);
+        self.run_part_test(2, self.part2.as_ref(), context);
+    }
+
+    pub fn run_actual(&self, context: &Context) {
+        self.run_part_actual(1, self.part1.as_ref(), context);
+        self.run_part_actual(2, self.part2.as_ref(), context);
+    }
+
+    pub fn run(&self, context: &Context) -> DayReport {
+        println!("~~~~~~~~~ {{ {} }} ~~~~~~~~~", format!("Day{:0>2}", self.id).yellow());
+        DayReport {
+            id: self.id,
+            part1: self.run_part(1, self.part1.as_ref(), context),
+            part2: self.run_part(2, self.part2.as_ref(), context),
+        }
     }
 
-    pub fn run_part1_test(&self) {
-        self.run_part_test(1, &self.part1);
+    /// Runs both parts without printing anything, test failures and missing inputs included in the report.
+    pub fn report(&self, context: &Context) -> DayReport {
+        DayReport {
+            id: self.id,
+            part1: self.report_part(1, self.part1.as_ref(), context),
+            part2: self.report_part(2, self.part2.as_ref(), context),
+        }
     }
 
-    pub fn run_part2_test(&self) {
-        self.run_part_test(2, &self.part2);
+    /// Panics unless the given part solves the example input as expected.
+    pub fn assert_example(&self, part: u8, context: &Context) {
+        let (outcome, duration) = match part {
+            1 => self.evaluate_part_test(self.part1.as_ref(), context),
+            2 => self.evaluate_part_test(self.part2.as_ref(), context),
+            _ => panic!("There is no part {}", part),
+        };
+        self.fail_test(part, &outcome, duration);
     }
 
-    pub fn run_test(&self) {
-        self.run_part_test(1, &self.part1);
-        self.run_part_test(2, &self.part2);
+    /// Panics unless the given part still produces the answer recorded for the actual input.
+    pub fn assert_actual(&self, part: u8, context: &Context) {
+        let (answer, _, _) = match part {
+            1 => self.evaluate_part_actual(self.part1.as_ref(), context),
+            2 => self.evaluate_part_actual(self.part2.as_ref(), context),
+            _ => panic!("There is no part {}", part),
+        };
+        let answer = answer.unwrap_or_else(|| panic!("Part {} input {} is missing", part, actual_input_path(self.id)));
+        let path = answer_path(self.id, part);
+        let expected = fs::read_to_string(&path).unwrap_or_else(|_| panic!("Part {} has no recorded answer at {}", part, path));
+        let expected = expected.trim_end_matches('\n');
+        assert_eq!(answer, expected, "Part {} answer differs from the one recorded at {}", part, path);
     }
 
-    pub fn run_actual(&self) {
-        self.run_part_actual(1, &self.part1);
-        self.run_part_actual(2, &self.part2);
+    /// Stores the current answers for the actual input so that [`Day::assert_actual`] can check them later.
+    pub fn record_answers(&self, context: &Context) -> DayReport {
+        let report = self.run(context);
+        for part in report.parts() {
+            if let Some(answer) = &part.answer {
+                let path = answer_path(self.id, part.id);
+                fs::write(&path, answer).unwrap();
+                println!("Recorded part {} answer to {}", part.id, path);
+            }
+        }
+        report
     }
 
-    pub fn run(&self) -> (Duration, Duration) {
+    /// Runs every implementation of each part on the same inputs, asserts that they agree and prints their timings side by side.
+    pub fn compare(&self, context: &Context) {
         println!("~~~~~~~~~ {{ {} }} ~~~~~~~~~", format!("Day{:0>2}", self.id).yellow());
-        self.run_part_test(1, &self.part1);
-        let first = self.run_part_actual(1, &self.part1);
-        self.run_part_test(2, &self.part2);
-        let second = self.run_part_actual(2, &self.part2);
-        (first, second)
+        Self::compare_part(1, Self::implementations(self.part1.as_ref(), &self.alternatives1), &self.test_input, &self.actual_input, context);
+        Self::compare_part(2, Self::implementations(self.part2.as_ref(), &self.alternatives2), &self.test_input, &self.actual_input, context);
+    }
+
+    fn implementations<'a, R: AocResult>(part: &'a dyn Part<R>, alternatives: &'a [(String, Box<dyn Part<R>>)]) -> Vec<(&'a str, &'a dyn Part<R>)> {
+        let mut result = vec![(DEFAULT_IMPLEMENTATION, part)];
+        result.extend(alternatives.iter().map(|(name, part)| (name.as_str(), part.as_ref())));
+        result
+    }
+
+    fn compare_part<R: AocResult>(id: u8, implementations: Vec<(&str, &dyn Part<R>)>, test_input: &Option<Vec<String>>, actual_input: &Option<Vec<String>>, context: &Context) {
+        if implementations[0].1.expect_test().to_string() == NOT_IMPLEMENTED {
+            return;
+        }
+
+        let run = |part: &dyn Part<R>, input: &Option<Vec<String>>| input.as_ref().map(|input| Self::timed(|| part.solve_with_context(input, context)));
+
+        let results = implementations.iter()
+            .map(|(name, part)| (*name, run(*part, test_input), run(*part, actual_input)))
+            .collect::<Vec<_>>();
+
+        let (reference_name, reference_test, reference_actual) = &results[0];
+        for (name, test, actual) in &results[1..] {
+            for (input_name, reference, other) in [("example", reference_test, test), ("actual", reference_actual, actual)] {
+                if let (Some((expected, _)), Some((got, _))) = (reference, other) {
+                    assert_eq!(got, expected, "Part {} implementations disagree on the {} input: {} returned {} but {} returned {}", id, input_name, reference_name, expected, name, got);
+                }
+            }
+        }
+
+        let fastest = results.iter().filter_map(|(_, _, actual)| actual.as_ref().map(|(_, d)| *d)).min();
+        let format_timing = |timing: &Option<(R, Duration)>| timing.as_ref().map(|(_, d)| format!("{:.1?}", d)).unwrap_or_else(|| "-".to_string());
+
+        println!("{}", format!("Part {} {:<16} {:>12} {:>12} {:>8}  answer", id, "implementation", "example", "actual", "relative").bold());
+        for (name, test, actual) in &results {
+            let relative = match (actual, fastest) {
+                (Some((_, d)), Some(fastest)) if !fastest.is_zero() => format!("{:.2}x", d.as_secs_f64() / fastest.as_secs_f64()),
+                _ => "-".to_string(),
+            };
+            let answer = match actual.as_ref().or(test.as_ref()) {
+                Some((answer, _)) if answer.to_string().lines().count() > 1 => format!("({} lines)", answer.to_string().lines().count()),
+                Some((answer, _)) => answer.to_string(),
+                None => "-".to_string(),
+            };
+            println!("       {:<16} {:>12} {:>12} {:>8}  {}", name, format_timing(test).purple(), format_timing(actual).purple(), relative, answer.blue());
+        }
     }
 
     pub fn f(self) -> DayRunner {
-        DayRunner::new(Box::new(move || self.run()))
+        let id = self.id;
+        let day = Rc::new(self);
+        let reporting_day = day.clone();
+        let comparing_day = day.clone();
+        let recording_day = day.clone();
+        DayRunner::new(
+            id,
+            Box::new(move |context| day.run(context)),
+            Box::new(move |context| reporting_day.report(context)),
+            Box::new(move |context| comparing_day.compare(context)),
+            Box::new(move |context| recording_day.record_answers(context)),
+        )
     }
 }
 
+const DEFAULT_IMPLEMENTATION: &str = "default";
+
 pub struct DayRunner {
-    pub f: Box<dyn Fn() -> (Duration, Duration)>,
+    pub id: u8,
+    pub f: Box<dyn Fn(&Context) -> DayReport>,
+    pub report: Box<dyn Fn(&Context) -> DayReport>,
+    pub compare: Box<dyn Fn(&Context)>,
+    pub record: Box<dyn Fn(&Context) -> DayReport>,
 }
 
 impl DayRunner {
-    pub fn new(f: Box<dyn Fn() -> (Duration, Duration)>) -> Self {
-        Self { f }
+    pub fn new(
+        id: u8,
+        f: Box<dyn Fn(&Context) -> DayReport>,
+        report: Box<dyn Fn(&Context) -> DayReport>,
+        compare: Box<dyn Fn(&Context)>,
+        record: Box<dyn Fn(&Context) -> DayReport>,
+    ) -> Self {
+        Self { id, f, report, compare, record }
     }
 }
 
-fn read_input(path: &str) -> Vec<String> {
-    fs::read_to_string(path).unwrap().split("\n").map(String::from).collect::<Vec<_>>()
+fn test_input_path(id: u8) -> String {
+    format!("input/{:0>2}_test.txt", id)
+}
+
+fn actual_input_path(id: u8) -> String {
+    format!("input/{:0>2}.txt", id)
+}
+
+fn answer_path(id: u8, part: u8) -> String {
+    format!("input/{:0>2}_part{}_answer.txt", id, part)
+}
+
+fn read_input(path: &str) -> Option<Vec<String>> {
+    fs::read_to_string(path).ok().map(|s| s.split('\n').map(String::from).collect::<Vec<_>>())
 }
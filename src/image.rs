@@ -0,0 +1,89 @@
+//! Minimal raster images written as binary PGM or PPM, for days that export pictures of their state.
+
+use std::fs;
+use std::io::Error;
+
+pub type Rgb = [u8; 3];
+
+pub const BLACK: Rgb = [0, 0, 0];
+pub const WHITE: Rgb = [255, 255, 255];
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, background: Rgb) -> Self {
+        Self { width, height, pixels: vec![background; width * height] }
+    }
+
+    /// An image with one pixel per cell of a grid.
+    pub fn from_grid<T>(grid: &[Vec<T>], color: impl Fn(&T) -> Rgb) -> Self {
+        let width = grid.iter().map(|row| row.len()).max().unwrap_or(0);
+        let mut image = Image::new(width, grid.len(), BLACK);
+        for (y, row) in grid.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                image.set(x, y, color(cell));
+            }
+        }
+        image
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Rgb {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Rgb) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    /// Enlarges every pixel to a `factor` by `factor` square, so small grids stay visible.
+    pub fn scaled(&self, factor: usize) -> Self {
+        let mut result = Image::new(self.width * factor, self.height * factor, BLACK);
+        for y in 0..result.height {
+            for x in 0..result.width {
+                result.set(x, y, self.get(x / factor, y / factor));
+            }
+        }
+        result
+    }
+
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut bytes = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        bytes.extend(self.pixels.iter().flatten());
+        bytes
+    }
+
+    /// The image in grayscale, using the luma of every pixel.
+    pub fn to_pgm(&self) -> Vec<u8> {
+        let mut bytes = format!("P5\n{} {}\n255\n", self.width, self.height).into_bytes();
+        bytes.extend(self.pixels.iter().map(|&[r, g, b]| ((299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000) as u8));
+        bytes
+    }
+
+    pub fn write_ppm(&self, path: &str) -> Result<(), Error> {
+        fs::write(path, self.to_ppm())
+    }
+
+    pub fn write_pgm(&self, path: &str) -> Result<(), Error> {
+        fs::write(path, self.to_pgm())
+    }
+}
+
+/// Maps `value` from 0 to 1 onto a black, red, yellow, white heat scale.
+pub fn heat(value: f64) -> Rgb {
+    let value = if value.is_nan() { 0.0 } else { value.clamp(0.0, 1.0) };
+    let channel = |offset: f64| ((value * 3.0 - offset).clamp(0.0, 1.0) * 255.0).round() as u8;
+    [channel(0.0), channel(1.0), channel(2.0)]
+}
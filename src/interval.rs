@@ -0,0 +1,258 @@
+//! Half-open integer intervals and sets of them, for days that reason about covered ranges.
+
+use std::cmp::{max, min};
+use std::fmt::{Debug, Display, Formatter};
+
+/// The integers `start..end`, empty whenever `end <= start`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Interval {
+    pub start: i64,
+    pub end: i64,
+}
+
+impl Interval {
+    pub const fn new(start: i64, end: i64) -> Self {
+        Self { start, end }
+    }
+
+    pub const fn inclusive(first: i64, last: i64) -> Self {
+        Self::new(first, last + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.end <= self.start
+    }
+
+    pub fn len(&self) -> u64 {
+        if self.is_empty() { 0 } else { self.start.abs_diff(self.end) }
+    }
+
+    pub fn contains(&self, value: i64) -> bool {
+        self.start <= value && value < self.end
+    }
+
+    /// Whether every value of `other` is also in `self`, which holds for any empty `other`.
+    pub fn contains_interval(&self, other: &Self) -> bool {
+        other.is_empty() || (self.start <= other.start && other.end <= self.end)
+    }
+
+    pub fn overlaps(&self, other: &Self) -> bool {
+        !self.intersection(other).is_empty()
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        Self::new(max(self.start, other.start), min(self.end, other.end))
+    }
+}
+
+impl Debug for Interval {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self, f)
+    }
+}
+
+/// A set of integers stored as sorted, disjoint and non-adjacent intervals.
+#[derive(Clone, Default, Eq, PartialEq)]
+pub struct IntervalSet {
+    intervals: Vec<Interval>,
+}
+
+impl IntervalSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intervals(&self) -> &[Interval] {
+        &self.intervals
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// Number of integers in the set.
+    pub fn len(&self) -> u64 {
+        self.intervals.iter().map(|i| i.len()).sum()
+    }
+
+    pub fn insert(&mut self, interval: Interval) {
+        if interval.is_empty() {
+            return;
+        }
+
+        // Every interval touching the new one gets merged into it
+        let first = self.intervals.partition_point(|i| i.end < interval.start);
+        let last = self.intervals.partition_point(|i| i.start <= interval.end);
+
+        let merged = self.intervals[first..last].iter().fold(interval, |acc, i| Interval::new(min(acc.start, i.start), max(acc.end, i.end)));
+        self.intervals.splice(first..last, [merged]);
+    }
+
+    pub fn contains(&self, value: i64) -> bool {
+        let index = self.intervals.partition_point(|i| i.end <= value);
+        self.intervals.get(index).is_some_and(|i| i.contains(value))
+    }
+
+    pub fn contains_interval(&self, interval: &Interval) -> bool {
+        interval.is_empty() || {
+            let index = self.intervals.partition_point(|i| i.end <= interval.start);
+            self.intervals.get(index).is_some_and(|i| i.contains_interval(interval))
+        }
+    }
+
+    /// Whether every value of `other` is also in `self`.
+    pub fn covers(&self, other: &Self) -> bool {
+        other.intervals.iter().all(|i| self.contains_interval(i))
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut result = self.clone();
+        for &interval in &other.intervals {
+            result.insert(interval);
+        }
+        result
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut intervals = Vec::new();
+        let (mut i, mut j) = (0, 0);
+
+        while i < self.intervals.len() && j < other.intervals.len() {
+            let (a, b) = (self.intervals[i], other.intervals[j]);
+            let overlap = a.intersection(&b);
+            if !overlap.is_empty() {
+                intervals.push(overlap);
+            }
+            if a.end < b.end { i += 1 } else { j += 1 }
+        }
+
+        Self { intervals }
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut intervals = Vec::new();
+        let mut j = 0;
+
+        for &interval in &self.intervals {
+            let mut start = interval.start;
+
+            while j < other.intervals.len() && other.intervals[j].end <= start {
+                j += 1;
+            }
+
+            let mut k = j;
+            while k < other.intervals.len() && other.intervals[k].start < interval.end {
+                let removed = other.intervals[k];
+                if removed.start > start {
+                    intervals.push(Interval::new(start, removed.start));
+                }
+                start = max(start, removed.end);
+                k += 1;
+            }
+
+            if start < interval.end {
+                intervals.push(Interval::new(start, interval.end));
+            }
+        }
+
+        Self { intervals }
+    }
+}
+
+impl FromIterator<Interval> for IntervalSet {
+    fn from_iter<T: IntoIterator<Item=Interval>>(iter: T) -> Self {
+        let mut result = Self::new();
+        for interval in iter {
+            result.insert(interval);
+        }
+        result
+    }
+}
+
+impl Debug for IntervalSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.intervals.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::{Interval, IntervalSet};
+
+    const RUNS: usize = 2000;
+
+    fn random_set(rng: &mut StdRng) -> (IntervalSet, BTreeSet<i64>) {
+        let intervals = (0..rng.gen_range(0..6))
+            .map(|_| {
+                let start = rng.gen_range(-20..20);
+                Interval::new(start, start + rng.gen_range(-2..10))
+            })
+            .collect::<Vec<_>>();
+
+        let brute_force = intervals.iter().flat_map(|i| i.start..i.end).collect();
+        (intervals.into_iter().collect(), brute_force)
+    }
+
+    fn to_points(set: &IntervalSet) -> BTreeSet<i64> {
+        set.intervals().iter().flat_map(|i| i.start..i.end).collect()
+    }
+
+    fn assert_normalized(set: &IntervalSet) {
+        assert!(set.intervals().iter().all(|i| !i.is_empty()), "{:?} contains an empty interval", set);
+        assert!(set.intervals().windows(2).all(|w| w[0].end < w[1].start), "{:?} is not sorted and disjoint", set);
+    }
+
+    #[test]
+    fn operations_match_brute_force() {
+        let mut rng = StdRng::seed_from_u64(2022);
+
+        for _ in 0..RUNS {
+            let (a, a_points) = random_set(&mut rng);
+            let (b, b_points) = random_set(&mut rng);
+
+            let cases = [
+                (a.union(&b), a_points.union(&b_points).copied().collect::<BTreeSet<_>>()),
+                (a.intersection(&b), a_points.intersection(&b_points).copied().collect()),
+                (a.difference(&b), a_points.difference(&b_points).copied().collect()),
+            ];
+
+            for (actual, expected) in cases {
+                assert_normalized(&actual);
+                assert_eq!(to_points(&actual), expected, "a = {:?}, b = {:?}", a, b);
+                assert_eq!(actual.len(), expected.len() as u64);
+            }
+
+            assert_eq!(a.covers(&b), b_points.is_subset(&a_points), "a = {:?}, b = {:?}", a, b);
+            for value in -25..35 {
+                assert_eq!(a.contains(value), a_points.contains(&value), "a = {:?}, value = {}", a, value);
+            }
+        }
+    }
+
+    #[test]
+    fn interval_relations_match_brute_force() {
+        let mut rng = StdRng::seed_from_u64(4);
+
+        for _ in 0..RUNS {
+            let a = Interval::new(rng.gen_range(-10..10), rng.gen_range(-10..10));
+            let b = Interval::new(rng.gen_range(-10..10), rng.gen_range(-10..10));
+            let a_points = (a.start..a.end).collect::<BTreeSet<_>>();
+            let b_points = (b.start..b.end).collect::<BTreeSet<_>>();
+
+            assert_eq!(a.contains_interval(&b), b_points.is_subset(&a_points), "{:?} {:?}", a, b);
+            assert_eq!(a.overlaps(&b), !a_points.is_disjoint(&b_points), "{:?} {:?}", a, b);
+            assert_eq!(a.intersection(&b).len(), a_points.intersection(&b_points).count() as u64, "{:?} {:?}", a, b);
+        }
+    }
+}
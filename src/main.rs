@@ -8,20 +8,21 @@ use std::env;
 use std::fmt::Debug;
 use std::io::Error;
 use std::process::{Command, ExitStatus, Stdio};
+use std::str::FromStr;
 use std::time::Duration;
 
-use crate::day01::day01;
+use crate::day01::{day01, report_calories};
 use crate::day02::day02;
 use crate::day03::day03;
 use crate::day04::day04;
 use crate::day05::day05;
 use crate::day06::day06;
-use crate::day07::day07;
-use crate::day08::day08;
-use crate::day09::day09;
+use crate::day07::{day07, show_file_system};
+use crate::day08::{day08, show_forest};
+use crate::day09::{animate, day09, AnimationOutput};
 use crate::day10::day10;
-use crate::day11::day11;
-use crate::day12::day12;
+use crate::day11::{day11, report_inspections};
+use crate::day12::{day12, draw_route};
 use crate::day13::day13;
 use crate::day14::day14;
 use crate::day15::day15;
@@ -35,9 +36,19 @@ use crate::day22::day22;
 use crate::day23::day23;
 use crate::day24::day24;
 use crate::day25::day25;
-use crate::harness::{AocResult, Day};
+use crate::dashboard::dashboard;
+use crate::harness::{AocResult, Context, Day, DayReport};
+use crate::report::write_html_report;
 
 mod harness;
+mod dashboard;
+mod image;
+mod interval;
+mod memory;
+mod ocr;
+mod report;
+#[cfg(test)]
+mod tests;
 mod day01;
 mod day02;
 mod day03;
@@ -94,17 +105,29 @@ fn main() {
     ];
 
 
-    let run_one = |id: usize| (days[id - 1].f)();
+    let args = env::args().collect::<Vec<_>>();
+
+    let html_path = option(&args, "--html");
+    let seed = option(&args, "--seed").map(|seed| seed.parse::<u64>().expect("Seed must be a number")).unwrap_or_else(rand::random);
+    let context = Context::new(seed);
+
+    let run_one = |id: usize| {
+        println!("Seed {}", context.seed);
+        (days[id - 1].f)(&context)
+    };
 
     let run_all = || {
-        let (p1, p2) = days.iter().map(|d| (d.f)()).unzip();
-        plot(p1, p2).unwrap();
+        println!("Seed {}", context.seed);
+        let reports = days.iter().map(|d| (d.f)(&context)).collect::<Vec<_>>();
+        if let Some(path) = html_path {
+            write_html_report(path, &reports, &context).unwrap();
+            println!("Report written to {}", path);
+        }
+        plot(&reports).unwrap();
     };
 
     let run_latest = || run_one(days.len());
 
-    let args = env::args().collect::<Vec<_>>();
-
     match args.get(1) {
         Some(arg) => {
             if let Ok(id) = arg.parse::<usize>() {
@@ -112,6 +135,47 @@ fn main() {
             } else {
                 match arg.as_str() {
                     "all" => { run_all(); }
+                    "dashboard" => { dashboard(&days, &context).unwrap(); }
+                    "compare" => {
+                        let id = args.get(2).and_then(|id| id.parse::<usize>().ok()).unwrap_or(days.len());
+                        (days[id - 1].compare)(&context);
+                    }
+                    "record" => {
+                        let id = args.get(2).and_then(|id| id.parse::<usize>().ok()).unwrap_or(days.len());
+                        (days[id - 1].record)(&context);
+                    }
+                    "calories" => {
+                        let input = option(&args, "--input").map_or("input/01_test.txt", |path| path.as_str());
+                        report_calories(input, number_option(&args, "--top", 3)).unwrap();
+                    }
+                    "tree" => {
+                        let input = option(&args, "--input").map_or("input/07_test.txt", |path| path.as_str());
+                        let svg_path = option(&args, "--svg").map(|path| path.as_str());
+                        show_file_system(input, svg_path, number_option(&args, "--width", 800.0), number_option(&args, "--height", 600.0)).unwrap();
+                    }
+                    "forest" => {
+                        let input = option(&args, "--input").map_or("input/08_test.txt", |path| path.as_str());
+                        show_forest(input, option(&args, "--heatmaps").map(|prefix| prefix.as_str()), number_option(&args, "--scale", 8)).unwrap();
+                    }
+                    "rope" => {
+                        let input = option(&args, "--input").map_or("input/09_test.txt", |path| path.as_str());
+                        let output = match option(&args, "--frames") {
+                            Some(directory) if args.iter().any(|arg| arg == "--ppm") => AnimationOutput::PpmFrames { directory: directory.clone(), scale: number_option(&args, "--scale", 8) },
+                            Some(directory) => AnimationOutput::TextFrames { directory: directory.clone() },
+                            None => AnimationOutput::Terminal { fps: number_option(&args, "--fps", 10) },
+                        };
+                        animate(input, number_option(&args, "--knots", 10), &output).unwrap();
+                    }
+                    "monkeys" => {
+                        let input = option(&args, "--input").map_or("input/11_test.txt", |path| path.as_str());
+                        let relief = args.iter().any(|arg| arg == "--relief");
+                        report_inspections(input, number_option(&args, "--rounds", 10000), relief, option(&args, "--csv").map(|path| path.as_str())).unwrap();
+                    }
+                    "climb" => {
+                        let input = option(&args, "--input").map_or("input/12_test.txt", |path| path.as_str());
+                        let from_lowest = args.iter().any(|arg| arg == "--from-lowest");
+                        draw_route(input, from_lowest, option(&args, "--ppm").map(|path| path.as_str()), number_option(&args, "--scale", 8)).unwrap();
+                    }
                     _ => { run_latest(); }
                 }
             }
@@ -120,15 +184,25 @@ fn main() {
     };
 }
 
-fn plot(part1: Vec<Duration>, part2: Vec<Duration>) -> Result<(), Error> {
-    let convert = |v: Vec<Duration>| v.into_iter().map(|d| d.as_micros()).map(|n| n.to_string()).collect::<Vec<_>>().join("#");
+/// The value following `name` on the command line.
+fn option<'a>(args: &'a [String], name: &str) -> Option<&'a String> {
+    args.iter().skip_while(|arg| *arg != name).nth(1)
+}
+
+/// The number following `name` on the command line, or `default` without one.
+fn number_option<T: FromStr>(args: &[String], name: &str, default: T) -> T {
+    option(args, name).map(|n| n.parse().unwrap_or_else(|_| panic!("{} must be a number", name))).unwrap_or(default)
+}
+
+fn plot(reports: &[DayReport]) -> Result<(), Error> {
+    let convert = |f: fn(&DayReport) -> Duration| reports.iter().map(f).map(|d| d.as_micros()).map(|n| n.to_string()).collect::<Vec<_>>().join("#");
 
     // Lol just plot with kotlin who's gonna stop me???
     Command::new("java")
         .arg("-jar")
         .arg("plotter.jar")
-        .arg(&convert(part1))
-        .arg(&convert(part2))
+        .arg(&convert(|r| r.part1.duration))
+        .arg(&convert(|r| r.part2.duration))
         .spawn()?
         .wait()?;
 
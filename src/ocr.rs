@@ -0,0 +1,134 @@
+//! Reads the block letters some puzzles draw, in the 6 pixel high font Advent of Code uses.
+
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
+pub const HEIGHT: usize = 6;
+
+/// Every known letter, most of them 4 pixels wide.
+const GLYPHS: [(char, [&str; HEIGHT]); 18] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', ["###", ".#.", ".#.", ".#.", ".#.", "###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum OcrError {
+    Height(usize),
+    /// The text read so far with `?` for every glyph that is not a known letter, and the columns of those glyphs.
+    Unrecognised { text: String, glyphs: Vec<Range<usize>> },
+}
+
+impl Display for OcrError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OcrError::Height(height) => write!(f, "Letters are {} pixels high but the drawing is {}", HEIGHT, height),
+            OcrError::Unrecognised { text, glyphs } => {
+                let columns = glyphs.iter().map(|c| format!("{}..{}", c.start, c.end)).collect::<Vec<_>>();
+                write!(f, "Unrecognised glyphs at columns {} in {:?}", columns.join(", "), text)
+            }
+        }
+    }
+}
+
+/// Reads a drawing where `#` or `█` are lit pixels and anything else is dark, glyphs being separated by dark columns.
+pub fn read_letters(drawing: &str) -> Result<String, OcrError> {
+    let rows = drawing.lines().filter(|line| !line.trim().is_empty()).map(|line| line.chars().map(is_lit).collect::<Vec<_>>()).collect::<Vec<_>>();
+    if rows.len() != HEIGHT {
+        return Err(OcrError::Height(rows.len()));
+    }
+
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let lit = |x: usize, y: usize| rows[y].get(x).copied().unwrap_or(false);
+
+    let mut text = String::new();
+    let mut unrecognised = Vec::new();
+    let mut x = 0;
+
+    while x < width {
+        if (0..HEIGHT).all(|y| !lit(x, y)) {
+            x += 1;
+            continue;
+        }
+
+        let start = x;
+        while x < width && (0..HEIGHT).any(|y| lit(x, y)) {
+            x += 1;
+        }
+
+        let glyph = (0..HEIGHT).map(|y| (start..x).map(|x| if lit(x, y) { '#' } else { '.' }).collect::<String>()).collect::<Vec<_>>();
+        match GLYPHS.iter().find(|(_, pattern)| pattern.iter().eq(glyph.iter())) {
+            Some((letter, _)) => text.push(*letter),
+            None => {
+                text.push('?');
+                unrecognised.push(start..x);
+            }
+        }
+    }
+
+    if unrecognised.is_empty() {
+        Ok(text)
+    } else {
+        Err(OcrError::Unrecognised { text, glyphs: unrecognised })
+    }
+}
+
+/// Draws `text` with one dark column between letters, the inverse of [`read_letters`].
+pub fn draw_letters(text: &str) -> Result<String, char> {
+    let glyphs = text.chars()
+        .map(|c| GLYPHS.iter().find(|(letter, _)| *letter == c).map(|(_, pattern)| pattern).ok_or(c))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let rows = (0..HEIGHT).map(|y| glyphs.iter().map(|pattern| pattern[y]).collect::<Vec<_>>().join(".")).collect::<Vec<_>>();
+    Ok(rows.join("\n"))
+}
+
+fn is_lit(c: char) -> bool {
+    c == '#' || c == '█'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{draw_letters, read_letters, OcrError, GLYPHS};
+
+    #[test]
+    fn reads_every_letter_back() {
+        let alphabet = GLYPHS.iter().map(|(letter, _)| *letter).collect::<String>();
+        assert_eq!(read_letters(&draw_letters(&alphabet).unwrap()), Ok(alphabet));
+    }
+
+    #[test]
+    fn reads_crt_output() {
+        let drawing = [
+            "███..█....███..█..█.████.",
+            "█..█.█....█..█.█..█.█....",
+            "█..█.█....█..█.█..█.███..",
+            "███..█....███..█..█.█....",
+            "█....█....█.█..█..█.█....",
+            "█....████.█..█..██..████.",
+        ].join("\n");
+        assert_eq!(read_letters(&drawing), Ok("PLRUE".to_string()));
+    }
+
+    #[test]
+    fn reports_unrecognised_glyphs() {
+        let drawing = draw_letters("AB").unwrap().lines().map(|line| format!("{}.#.{}", line, "####")).collect::<Vec<_>>().join("\n");
+        assert_eq!(read_letters(&drawing), Err(OcrError::Unrecognised { text: "AB??".to_string(), glyphs: vec![10..11, 12..16] }));
+        assert_eq!(read_letters("#\n#"), Err(OcrError::Height(2)));
+    }
+}
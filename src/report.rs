@@ -0,0 +1,156 @@
+//! Self-contained HTML summary of a full run, with an inline SVG chart of the runtimes.
+
+use std::fmt::Write;
+use std::fs;
+use std::io::Error;
+use std::time::Duration;
+
+use crate::harness::{Context, DayReport, PartReport, TestOutcome};
+use crate::memory::format_bytes;
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; background: #0f0f23; color: #cccccc; margin: 2em; }
+h1 { color: #ffff66; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { padding: 0.3em 0.8em; border-bottom: 1px solid #333340; text-align: left; vertical-align: top; }
+th { color: #ffff66; }
+td.number { text-align: right; font-family: monospace; }
+.answer, pre { font-family: monospace; color: #009900; }
+pre { margin: 0.3em 0; line-height: 1.1; }
+.passed { color: #00cc00; }
+.failed { color: #ff3333; }
+.muted { color: #666666; }
+summary { cursor: pointer; }
+"#;
+
+const CHART_WIDTH: f64 = 800.0;
+const CHART_LABEL_WIDTH: f64 = 60.0;
+const CHART_BAR_HEIGHT: f64 = 8.0;
+const CHART_ROW_HEIGHT: f64 = 22.0;
+const PART_COLORS: [&str; 2] = ["#9999cc", "#ffff66"];
+
+pub fn write_html_report(path: &str, reports: &[DayReport], context: &Context) -> Result<(), Error> {
+    fs::write(path, html_report(reports, context))
+}
+
+pub fn html_report(reports: &[DayReport], context: &Context) -> String {
+    let total = reports.iter().map(|r| r.duration()).sum::<Duration>();
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>Advent of Code 2022</title>\n");
+    write!(html, "<style>{}</style>\n</head>\n<body>\n", STYLE).unwrap();
+    write!(html, "<h1>Advent of Code 2022</h1>\n<p>{} days, total runtime {}, seed {}</p>\n", reports.len(), format_duration(total), context.seed).unwrap();
+    html.push_str(&table(reports));
+    html.push_str(&chart(reports));
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn table(reports: &[DayReport]) -> String {
+    let mut html = String::from("<table>\n<tr><th>Day</th><th>Part</th><th>Example</th><th>Answer</th><th>Runtime</th><th>Peak memory</th></tr>\n");
+
+    for report in reports {
+        for part in report.parts() {
+            let day = if part.id == 1 { format!("<td rowspan=\"2\">Day{:0>2}</td>", report.id) } else { String::new() };
+            writeln!(
+                html,
+                "<tr>{}<td>{}</td><td>{}</td><td>{}</td><td class=\"number\">{}</td><td class=\"number\">{}</td></tr>",
+                day,
+                part.id,
+                test_cell(part),
+                answer_cell(part),
+                if part.answer.is_some() { format_duration(part.duration) } else { String::new() },
+                if part.answer.is_some() { format_bytes(part.memory) } else { String::new() },
+            ).unwrap();
+        }
+    }
+
+    html.push_str("</table>\n");
+    html
+}
+
+fn test_cell(part: &PartReport) -> String {
+    match &part.test {
+        TestOutcome::Passed => format!("<span class=\"passed\">passed</span> <span class=\"muted\">{}</span>", format_duration(part.test_duration)),
+        TestOutcome::Failed { expected, actual } => format!(
+            "<span class=\"failed\">failed</span> expected <span class=\"answer\">{}</span> but got <span class=\"answer\">{}</span>",
+            escape(expected),
+            escape(actual),
+        ),
+        TestOutcome::NotImplemented => "<span class=\"muted\">not implemented</span>".to_string(),
+        TestOutcome::MissingInput => "<span class=\"muted\">missing input</span>".to_string(),
+    }
+}
+
+fn answer_cell(part: &PartReport) -> String {
+    match &part.answer {
+        Some(answer) if answer.lines().count() > 1 => format!(
+            "<details><summary>{} lines</summary><pre>{}</pre></details>",
+            answer.lines().count(),
+            escape(answer),
+        ),
+        Some(answer) => format!("<span class=\"answer\">{}</span>", escape(answer)),
+        None => "<span class=\"muted\">-</span>".to_string(),
+    }
+}
+
+/// Horizontal bar chart of both parts' runtimes per day on a logarithmic axis, from 1µs up to the slowest part.
+fn chart(reports: &[DayReport]) -> String {
+    let micros = |d: Duration| (d.as_secs_f64() * 1_000_000.0).max(1.0);
+
+    let max_exponent = reports.iter()
+        .flat_map(|r| r.parts())
+        .map(|p| micros(p.duration).log10().ceil() as u32)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let plot_width = CHART_WIDTH - CHART_LABEL_WIDTH;
+    let x = |d: Duration| CHART_LABEL_WIDTH + micros(d).log10() / max_exponent as f64 * plot_width;
+    let height = reports.len() as f64 * CHART_ROW_HEIGHT + 30.0;
+
+    let mut svg = String::new();
+    writeln!(svg, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-size=\"11\" font-family=\"monospace\">", CHART_WIDTH + 20.0, height).unwrap();
+
+    for exponent in 0..=max_exponent {
+        let tick_x = CHART_LABEL_WIDTH + exponent as f64 / max_exponent as f64 * plot_width;
+        let label = format_duration(Duration::from_micros(10_u64.pow(exponent)));
+        writeln!(svg, "<line x1=\"{0:.1}\" y1=\"0\" x2=\"{0:.1}\" y2=\"{1:.1}\" stroke=\"#333340\"/>", tick_x, height - 20.0).unwrap();
+        writeln!(svg, "<text x=\"{:.1}\" y=\"{:.1}\" fill=\"#666666\" text-anchor=\"middle\">{}</text>", tick_x, height - 5.0, label).unwrap();
+    }
+
+    for (row, report) in reports.iter().enumerate() {
+        let y = row as f64 * CHART_ROW_HEIGHT + 2.0;
+        writeln!(svg, "<text x=\"0\" y=\"{:.1}\" fill=\"#cccccc\">Day{:0>2}</text>", y + CHART_BAR_HEIGHT + 3.0, report.id).unwrap();
+
+        for (i, part) in report.parts().iter().enumerate() {
+            if part.answer.is_none() {
+                continue;
+            }
+            writeln!(
+                svg,
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{}\" fill=\"{}\"><title>Day{:0>2} part {}: {}</title></rect>",
+                CHART_LABEL_WIDTH,
+                y + i as f64 * (CHART_BAR_HEIGHT + 1.0),
+                x(part.duration) - CHART_LABEL_WIDTH,
+                CHART_BAR_HEIGHT,
+                PART_COLORS[i],
+                report.id,
+                part.id,
+                format_duration(part.duration),
+            ).unwrap();
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.1?}", duration)
+}
+
+/// Escapes text for HTML and SVG content and attributes.
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
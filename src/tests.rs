@@ -0,0 +1,6 @@
+//! One test per day and part, generated by `build.rs` from the files present in `input/`.
+//!
+//! Example tests are ignored when their input is missing, tests against the actual input are always ignored
+//! and need both `input/NN.txt` and the answers recorded with `cargo run -- record NN`.
+
+include!(concat!(env!("OUT_DIR"), "/day_tests.rs"));